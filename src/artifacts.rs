@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Most artifacts kept; the oldest are dropped first
+const MAX_ARTIFACTS: usize = 64;
+
+/// Most bytes kept across all artifacts
+const MAX_TOTAL_BYTES: usize = 64 * 1024 * 1024;
+
+/// Full output of a command whose result was truncated
+struct Artifact {
+    artifact_id: String,
+    /// Caller whose command produced it; `None` for stdio
+    owner: Option<String>,
+    command: String,
+    created_at: DateTime<Utc>,
+    text: String,
+}
+
+/// Lines read from an artifact
+#[derive(Debug)]
+pub struct ArtifactPage {
+    pub artifact_id: String,
+    pub command: String,
+    pub created_at: DateTime<Utc>,
+    pub total_lines: usize,
+    /// Lines returned, with their 1-based line numbers
+    pub lines: Vec<(usize, String)>,
+    /// Whether more lines matched after the ones returned
+    pub more: bool,
+}
+
+/// Full outputs of truncated `execute` results, kept in memory so agents
+/// can page through or search them later
+#[derive(Default)]
+pub struct ArtifactStore {
+    artifacts: Mutex<VecDeque<Artifact>>,
+}
+
+impl ArtifactStore {
+    /// Keep the full output of `command`, already redacted. Returns its ID.
+    pub fn save(&self, owner: Option<String>, command: &str, text: String) -> String {
+        let artifact_id = format!("out-{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let mut artifacts = self.artifacts.lock().unwrap();
+        artifacts.push_back(Artifact {
+            artifact_id: artifact_id.clone(),
+            owner,
+            command: command.to_string(),
+            created_at: Utc::now(),
+            text,
+        });
+        let mut total: usize = artifacts.iter().map(|a| a.text.len()).sum();
+        while artifacts.len() > MAX_ARTIFACTS || (total > MAX_TOTAL_BYTES && artifacts.len() > 1) {
+            if let Some(dropped) = artifacts.pop_front() {
+                total -= dropped.text.len();
+            }
+        }
+        artifact_id
+    }
+
+    /// Up to `limit` lines of an artifact from `start_line` (1-based), or
+    /// only the ones matching `pattern`
+    pub fn read(
+        &self,
+        artifact_id: &str,
+        caller: Option<&str>,
+        start_line: usize,
+        limit: usize,
+        pattern: Option<&Regex>,
+    ) -> Result<ArtifactPage, String> {
+        let artifacts = self.artifacts.lock().unwrap();
+        let artifact = artifacts
+            .iter()
+            .find(|a| a.artifact_id == artifact_id)
+            .ok_or_else(|| format!("Artifact {} not found; artifacts are dropped when the server restarts or needs room", artifact_id))?;
+        if artifact.owner.as_deref() != caller {
+            return Err(format!("Artifact {} belongs to another client", artifact_id));
+        }
+
+        let total_lines = artifact.text.lines().count();
+        let mut matching = artifact
+            .text
+            .lines()
+            .enumerate()
+            .skip(start_line.saturating_sub(1))
+            .filter(|(_, line)| pattern.is_none_or(|p| p.is_match(line)))
+            .map(|(i, line)| (i + 1, line.to_string()));
+        let lines: Vec<(usize, String)> = matching.by_ref().take(limit).collect();
+        Ok(ArtifactPage {
+            artifact_id: artifact.artifact_id.clone(),
+            command: artifact.command.clone(),
+            created_at: artifact.created_at,
+            total_lines,
+            lines,
+            more: matching.next().is_some(),
+        })
+    }
+}
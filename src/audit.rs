@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::redact::Redactor;
+
+/// How command and input text is written to the audit log
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditRedaction {
+    /// Record text verbatim
+    None,
+    /// Replace detected secrets with redaction markers
+    Secrets,
+    /// Replace text with its length only
+    Full,
+}
+
+/// A single audit log entry, written as one JSON line
+#[derive(Debug, Default, serde::Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// Tool that was called
+    pub tool: &'static str,
+    /// Identity of the MCP client that made the call
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<u32>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Append-only JSON Lines audit log of tool calls.
+///
+/// Disabled unless `MCP_TERMINAL_AUDIT_LOG` names a file.
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+    redaction: AuditRedaction,
+    redactor: Arc<Redactor>,
+}
+
+impl AuditLog {
+    /// Configure the audit log from environment variables:
+    /// `MCP_TERMINAL_AUDIT_LOG` (file path) and
+    /// `MCP_TERMINAL_AUDIT_REDACT` (`none`, `secrets` or `full`)
+    pub fn from_env(redactor: Arc<Redactor>) -> Result<Self, String> {
+        let redaction = match std::env::var("MCP_TERMINAL_AUDIT_REDACT").as_deref() {
+            Err(_) | Ok("") | Ok("none") => AuditRedaction::None,
+            Ok("secrets") => AuditRedaction::Secrets,
+            Ok("full") => AuditRedaction::Full,
+            Ok(other) => return Err(format!("Invalid MCP_TERMINAL_AUDIT_REDACT: {}", other)),
+        };
+
+        let file = match std::env::var("MCP_TERMINAL_AUDIT_LOG") {
+            Ok(path) if !path.is_empty() => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|e| format!("Failed to open audit log {}: {}", path, e))?;
+                tracing::info!(path = %path, "Audit log enabled");
+                Some(Mutex::new(file))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            file,
+            redaction,
+            redactor,
+        })
+    }
+
+    /// Apply the configured redaction to command or input text
+    fn redact(&self, text: &str) -> String {
+        match self.redaction {
+            AuditRedaction::None => text.to_string(),
+            AuditRedaction::Secrets => self.redactor.redact(text).0,
+            AuditRedaction::Full => format!("[redacted {} bytes]", text.len()),
+        }
+    }
+
+    /// Append a record to the log. Write failures are logged, not propagated —
+    /// a broken audit log should not take tool calls down with it.
+    pub fn record(&self, mut record: AuditRecord) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        record.command = record.command.map(|c| self.redact(&c));
+        record.input = record.input.map(|i| self.redact(&i));
+
+        let mut line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        let mut file = file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()).and_then(|_| file.flush()) {
+            tracing::error!("Failed to write audit log: {}", e);
+        }
+    }
+
+    /// Flush the log to disk before the server exits
+    pub fn flush(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Err(e) = file.lock().unwrap().sync_all() {
+            tracing::error!("Failed to flush audit log: {}", e);
+        }
+    }
+}
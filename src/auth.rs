@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+/// What a client of a network transport may do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// List sessions and read their output
+    Observe,
+    /// Create, write to and close sessions; implies `observe`
+    Sessions,
+    /// Run one-off commands with `execute`
+    Execute,
+    /// Receive server log messages, which cover the sessions of every client
+    Logs,
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Observe => "observe",
+            Self::Sessions => "sessions",
+            Self::Execute => "execute",
+            Self::Logs => "logs",
+        })
+    }
+}
+
+/// A client allowed to connect over HTTP, identified by a bearer token or
+/// the subject common name of its TLS client certificate
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Client {
+    /// Name recorded in logs and the audit log
+    pub name: String,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    cert_subject: Option<String>,
+    scopes: Vec<Scope>,
+    /// Projects whose sessions the client may use; all projects when unset
+    #[serde(default)]
+    projects: Option<Vec<String>>,
+}
+
+impl Client {
+    /// Whether the client holds `scope`
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+            || (scope == Scope::Observe && self.scopes.contains(&Scope::Sessions))
+    }
+
+    /// Whether the client may use sessions tagged with `project`
+    pub fn allows_project(&self, project: Option<&str>) -> bool {
+        match &self.projects {
+            None => true,
+            Some(allowed) => project.is_some_and(|p| allowed.iter().any(|a| a == p)),
+        }
+    }
+
+    /// Check that the client holds `scope`
+    pub fn check_scope(&self, scope: Scope) -> Result<(), String> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            tracing::warn!(client = %self.name, scope = %scope, "Denied request: missing scope");
+            Err(format!("Client {} lacks the {} scope", self.name, scope))
+        }
+    }
+
+    /// Check that the client holds `scope` and may use sessions of `project`
+    pub fn authorize(&self, scope: Scope, project: Option<&str>) -> Result<(), String> {
+        self.check_scope(scope)?;
+        if self.allows_project(project) {
+            return Ok(());
+        }
+        tracing::warn!(client = %self.name, project = ?project, "Denied request: project not allowed");
+        Err(match project {
+            Some(project) => format!("Client {} may not use project {}", self.name, project),
+            None => format!("Client {} may only use sessions of its allowed projects", self.name),
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientsFile {
+    clients: Vec<Client>,
+}
+
+/// Clients allowed to connect over network transports.
+///
+/// Disabled unless `MCP_TERMINAL_AUTH_FILE` names a JSON file of clients.
+pub struct Authenticator {
+    clients: Vec<Arc<Client>>,
+}
+
+impl Authenticator {
+    /// Load clients from the JSON file named by `MCP_TERMINAL_AUTH_FILE`.
+    /// Returns `None` when it is unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let path = match std::env::var("MCP_TERMINAL_AUTH_FILE") {
+            Ok(path) if !path.is_empty() => path,
+            _ => return Ok(None),
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read auth file {}: {}", path, e))?;
+        let file: ClientsFile = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid auth file {}: {}", path, e))?;
+
+        for client in &file.clients {
+            if client.token.is_none() && client.cert_subject.is_none() {
+                return Err(format!(
+                    "Invalid auth file {}: client {} needs a token or cert_subject",
+                    path, client.name
+                ));
+            }
+            if client.token.as_ref().is_some_and(|t| t.len() < 16) {
+                return Err(format!(
+                    "Invalid auth file {}: token of client {} is shorter than 16 characters",
+                    path, client.name
+                ));
+            }
+        }
+
+        tracing::info!(path = %path, clients = file.clients.len(), "Client authentication enabled");
+        Ok(Some(Self {
+            clients: file.clients.into_iter().map(Arc::new).collect(),
+        }))
+    }
+
+    /// Find the client holding a bearer token
+    pub fn by_token(&self, token: &str) -> Option<Arc<Client>> {
+        self.clients
+            .iter()
+            .find(|c| c.token.as_deref().is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes())))
+            .cloned()
+    }
+
+    /// Find the client with a verified TLS certificate for `subject`
+    pub fn by_cert_subject(&self, subject: &str) -> Option<Arc<Client>> {
+        self.clients
+            .iter()
+            .find(|c| c.cert_subject.as_deref() == Some(subject))
+            .cloned()
+    }
+}
+
+/// Compare secrets without leaking the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
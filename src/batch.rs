@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::process::ResourceUsage;
+use crate::redact::Redactions;
+use crate::session::SessionManager;
+
+/// Most commands in one batch
+pub const MAX_COMMANDS: usize = 50;
+
+/// Most commands `execute_batch` runs at once unless told otherwise
+pub const DEFAULT_MAX_PARALLEL: usize = 4;
+
+/// How the commands of a batch are scheduled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// One after another, in order
+    Sequential,
+    /// Up to this many at once, started in order
+    Parallel(usize),
+}
+
+impl Mode {
+    pub fn parse(mode: Option<&str>, max_parallel: Option<usize>) -> Result<Self, String> {
+        match mode {
+            None | Some("sequential") => Ok(Self::Sequential),
+            Some("parallel") => match max_parallel.unwrap_or(DEFAULT_MAX_PARALLEL) {
+                0 => Err("max_parallel must be at least 1".to_string()),
+                max => Ok(Self::Parallel(max)),
+            },
+            Some(other) => Err(format!("Unknown batch mode {}; use sequential or parallel", other)),
+        }
+    }
+}
+
+/// Outcome of one command of a batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Succeeded,
+    /// Exited with a non-zero code
+    Failed,
+    /// Could not run or timed out
+    Error,
+    /// Not started because an earlier command failed
+    Skipped,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct CommandResult {
+    pub command: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<u32>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
+    pub output: String,
+    /// Secrets redacted from the output
+    #[serde(skip_serializing_if = "Redactions::is_empty")]
+    pub redactions: Redactions,
+    /// What was left out of the output to fit the limits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<String>,
+    /// Artifact holding the full output, if it was truncated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CommandResult {
+    fn skipped(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            status: Status::Skipped,
+            exit_code: None,
+            duration_ms: 0,
+            usage: None,
+            output: String::new(),
+            redactions: Redactions::new(),
+            truncated: None,
+            artifact_id: None,
+            error: None,
+        }
+    }
+}
+
+/// Run `commands` with `execute`, in `cwd` and each limited to
+/// `timeout_secs`. With `stop_on_failure`, commands not yet started when
+/// one fails are skipped. Results are in the order of `commands`.
+pub fn run(
+    manager: &SessionManager,
+    commands: &[String],
+    cwd: Option<&str>,
+    timeout_secs: Option<u64>,
+    mode: Mode,
+    stop_on_failure: bool,
+) -> Vec<CommandResult> {
+    let failed = AtomicBool::new(false);
+    let run_one = |command: &str| {
+        if stop_on_failure && failed.load(Ordering::SeqCst) {
+            return CommandResult::skipped(command);
+        }
+        let result = execute(manager, command, cwd, timeout_secs);
+        if result.status != Status::Succeeded {
+            failed.store(true, Ordering::SeqCst);
+        }
+        result
+    };
+
+    let workers = match mode {
+        Mode::Sequential => return commands.iter().map(|c| run_one(c)).collect(),
+        Mode::Parallel(max) => max.min(commands.len()),
+    };
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<CommandResult>>> = Mutex::new(commands.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(command) = commands.get(i) else {
+                    break;
+                };
+                let result = run_one(command);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+fn execute(manager: &SessionManager, command: &str, cwd: Option<&str>, timeout_secs: Option<u64>) -> CommandResult {
+    let started = Instant::now();
+    match manager.execute(command, cwd.map(str::to_string), timeout_secs) {
+        Ok(result) => CommandResult {
+            command: command.to_string(),
+            status: if result.exit_code == 0 { Status::Succeeded } else { Status::Failed },
+            exit_code: Some(result.exit_code),
+            duration_ms: result.usage.wall_time_ms,
+            usage: Some(result.usage),
+            output: result.stdout,
+            redactions: result.redactions,
+            truncated: None,
+            artifact_id: None,
+            error: None,
+        },
+        Err(e) => CommandResult {
+            command: command.to_string(),
+            status: Status::Error,
+            exit_code: None,
+            duration_ms: started.elapsed().as_millis() as u64,
+            usage: None,
+            output: String::new(),
+            redactions: Redactions::new(),
+            truncated: None,
+            artifact_id: None,
+            error: Some(e),
+        },
+    }
+}
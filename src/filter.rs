@@ -0,0 +1,135 @@
+use chrono::Utc;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::session::SessionInfo;
+
+/// One requirement of a label selector
+#[derive(Debug)]
+enum Requirement {
+    Equals(String, String),
+    /// Also met by sessions without the label
+    NotEquals(String, String),
+    Exists(String),
+    NotExists(String),
+}
+
+/// Comma-separated label requirements, all of which must hold, e.g.
+/// `env=dev,tier!=db,team,!deprecated`
+#[derive(Debug)]
+pub struct Selector(Vec<Requirement>);
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        let mut requirements = Vec::new();
+        for part in selector.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let requirement = if let Some((key, value)) = part.split_once("!=") {
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some((key, value)) = part.split_once("==").or_else(|| part.split_once('=')) {
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some(key) = part.strip_prefix('!') {
+                Requirement::NotExists(key.trim().to_string())
+            } else {
+                Requirement::Exists(part.to_string())
+            };
+            let key = match &requirement {
+                Requirement::Equals(key, _)
+                | Requirement::NotEquals(key, _)
+                | Requirement::Exists(key)
+                | Requirement::NotExists(key) => key,
+            };
+            if key.is_empty() {
+                return Err(format!("Invalid label selector {}: missing label name in {}", selector, part));
+            }
+            requirements.push(requirement);
+        }
+        Ok(Self(requirements))
+    }
+
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.0.iter().all(|requirement| match requirement {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::NotExists(key) => !labels.contains_key(key),
+        })
+    }
+}
+
+/// Conditions a session must meet to be listed. Unset conditions match
+/// every session.
+#[derive(Debug, Default)]
+pub struct SessionFilter {
+    pub labels: Option<Selector>,
+    pub alive: Option<bool>,
+    /// Shell program, as a path or just its name (e.g. `zsh`)
+    pub shell: Option<String>,
+    pub min_age: Option<Duration>,
+    pub max_age: Option<Duration>,
+    /// Text the foreground process's command line contains (e.g. `cargo`)
+    pub foreground: Option<String>,
+}
+
+impl SessionFilter {
+    pub fn matches(&self, info: &SessionInfo) -> bool {
+        let age = (Utc::now() - info.created_at).to_std().unwrap_or_default();
+        let shell_name = info.shell_program.rsplit('/').next().unwrap_or_default();
+        self.labels.as_ref().is_none_or(|s| s.matches(&info.labels))
+            && self.alive.is_none_or(|alive| info.is_alive == alive)
+            && self
+                .shell
+                .as_deref()
+                .is_none_or(|shell| shell == info.shell_program || shell == shell_name)
+            && self.min_age.is_none_or(|min| age >= min)
+            && self.max_age.is_none_or(|max| age <= max)
+            && self.foreground.as_deref().is_none_or(|text| {
+                info.foreground
+                    .as_ref()
+                    .is_some_and(|f| f.command.contains(text) || f.name == text)
+            })
+    }
+}
+
+/// Order of listed sessions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Created,
+    Idle,
+    Name,
+    Project,
+}
+
+impl SortKey {
+    pub fn parse(key: Option<&str>) -> Result<Self, String> {
+        match key {
+            None | Some("created") | Some("created_at") => Ok(Self::Created),
+            Some("idle") | Some("idle_secs") => Ok(Self::Idle),
+            Some("name") => Ok(Self::Name),
+            Some("project") => Ok(Self::Project),
+            Some(other) => Err(format!("Unknown sort key {}; use created, idle, name or project", other)),
+        }
+    }
+}
+
+/// Sort sessions by `key`, ties broken by creation time. Sessions without
+/// a name or project come last when sorting by it, in either direction.
+pub fn sort(sessions: &mut [SessionInfo], key: SortKey, descending: bool) {
+    let direction = |ordering: Ordering| if descending { ordering.reverse() } else { ordering };
+    let missing_last = |a: &Option<String>, b: &Option<String>| match (a, b) {
+        (Some(a), Some(b)) => direction(a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+
+    sessions.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Created => Ordering::Equal,
+            SortKey::Idle => direction(a.idle_secs.cmp(&b.idle_secs)),
+            SortKey::Name => missing_last(&a.name, &b.name),
+            SortKey::Project => missing_last(&a.project, &b.project),
+        };
+        ordering.then_with(|| direction(a.created_at.cmp(&b.created_at)))
+    });
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+/// Maximum number of commands remembered per session
+const MAX_COMMANDS: usize = 1000;
+
+/// Maximum number of input lines waiting for the shell to start them
+const MAX_PENDING: usize = 100;
+
+/// A command run in a session
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Command {
+    /// Increasing number of the command within the session
+    pub command_id: u64,
+    /// Command line, as sent with `send_input` or, failing that, as echoed
+    /// by the shell
+    pub input: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Reported by shell integration; unknown without it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Range of the session's output the command produced, as byte offsets
+    /// from the start of the session. Open while the command runs.
+    pub output_start: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_end: Option<u64>,
+}
+
+/// Commands run in a session. With shell integration, commands start and
+/// finish at the shell's markers and take their input from the lines sent
+/// at the prompt; lines sent while a command runs are its input, not
+/// commands. Without it, every line sent starts a command and ends the
+/// previous one.
+#[derive(Default)]
+pub struct History {
+    commands: VecDeque<Command>,
+    next_id: u64,
+    /// Line being typed, until Enter
+    partial: String,
+    /// Complete lines the shell hasn't started yet
+    pending: VecDeque<String>,
+}
+
+impl History {
+    /// Record input sent to the session, whose output is at `offset`
+    pub fn input(&mut self, text: &str, offset: u64, integrated: bool) {
+        for c in text.chars() {
+            match c {
+                '\r' | '\n' => {
+                    let line = std::mem::take(&mut self.partial);
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    if !integrated {
+                        self.finish(None, offset);
+                        self.start(line, offset);
+                    } else if !self.running() {
+                        self.pending.push_back(line);
+                        if self.pending.len() > MAX_PENDING {
+                            self.pending.pop_front();
+                        }
+                    }
+                }
+                // Ctrl-C and other control keys abandon the line
+                c if c.is_control() && c != '\t' => self.partial.clear(),
+                c => self.partial.push(c),
+            }
+        }
+    }
+
+    /// The shell started a command whose output begins at `offset`. `echo`
+    /// is the command line it showed, escapes stripped.
+    pub fn started(&mut self, echo: &str, offset: u64) {
+        self.finish(None, offset);
+        let echo = echo.trim();
+        let input = match self.pending.pop_front() {
+            Some(line) if echo.is_empty() || echo.contains(line.trim()) => line,
+            // Typed some other way, so what was sent is stale
+            _ => {
+                self.pending.clear();
+                echo.to_string()
+            }
+        };
+        self.start(input, offset);
+    }
+
+    fn running(&self) -> bool {
+        self.commands.back().is_some_and(|c| c.finished_at.is_none())
+    }
+
+    /// The shell finished the running command, its output ending at `offset`
+    pub fn finished(&mut self, exit_code: Option<i32>, offset: u64) {
+        self.finish(exit_code, offset);
+    }
+
+    fn start(&mut self, input: String, offset: u64) {
+        self.next_id += 1;
+        self.commands.push_back(Command {
+            command_id: self.next_id,
+            input,
+            started_at: Utc::now(),
+            finished_at: None,
+            exit_code: None,
+            output_start: offset,
+            output_end: None,
+        });
+        if self.commands.len() > MAX_COMMANDS {
+            self.commands.pop_front();
+        }
+    }
+
+    fn finish(&mut self, exit_code: Option<i32>, offset: u64) {
+        if let Some(command) = self.commands.back_mut().filter(|c| c.finished_at.is_none()) {
+            command.finished_at = Some(Utc::now());
+            command.exit_code = exit_code;
+            command.output_end = Some(offset);
+        }
+    }
+
+    /// Commands after `after_id`, oldest first, at most `limit` of the newest
+    pub fn list(&self, after_id: Option<u64>, limit: usize) -> Vec<Command> {
+        let matching: Vec<&Command> = self
+            .commands
+            .iter()
+            .filter(|c| after_id.is_none_or(|after| c.command_id > after))
+            .collect();
+        matching[matching.len().saturating_sub(limit)..]
+            .iter()
+            .map(|c| (*c).clone())
+            .collect()
+    }
+
+    pub fn get(&self, command_id: u64) -> Option<Command> {
+        self.commands.iter().find(|c| c.command_id == command_id).cloned()
+    }
+}
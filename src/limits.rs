@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Default time an exited session stays readable
+const DEFAULT_EXITED_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Limits on how many sessions may exist and how long they may live.
+///
+/// Every limit is disabled when its environment variable is unset or `0`,
+/// except exited-session retention which defaults to one hour.
+#[derive(Clone, Debug)]
+pub struct SessionLimits {
+    /// Reap sessions with no input or output for this long
+    pub idle_timeout: Option<Duration>,
+    /// Reap sessions older than this, regardless of activity
+    pub max_lifetime: Option<Duration>,
+    /// Maximum number of live sessions
+    pub max_sessions: Option<usize>,
+    /// Maximum number of live sessions per project
+    pub max_sessions_per_project: Option<usize>,
+    /// How long a session whose shell has exited stays readable before it is removed
+    pub exited_retention: Duration,
+}
+
+impl SessionLimits {
+    /// Read limits from `MCP_TERMINAL_IDLE_TIMEOUT_SECS`,
+    /// `MCP_TERMINAL_MAX_LIFETIME_SECS`, `MCP_TERMINAL_MAX_SESSIONS`,
+    /// `MCP_TERMINAL_MAX_SESSIONS_PER_PROJECT` and
+    /// `MCP_TERMINAL_EXITED_RETENTION_SECS`
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            idle_timeout: env_u64("MCP_TERMINAL_IDLE_TIMEOUT_SECS")?.map(Duration::from_secs),
+            max_lifetime: env_u64("MCP_TERMINAL_MAX_LIFETIME_SECS")?.map(Duration::from_secs),
+            max_sessions: env_u64("MCP_TERMINAL_MAX_SESSIONS")?.map(|n| n as usize),
+            max_sessions_per_project: env_u64("MCP_TERMINAL_MAX_SESSIONS_PER_PROJECT")?
+                .map(|n| n as usize),
+            exited_retention: match std::env::var("MCP_TERMINAL_EXITED_RETENTION_SECS") {
+                Ok(value) if !value.is_empty() => Duration::from_secs(value.parse().map_err(|_| {
+                    format!("Invalid MCP_TERMINAL_EXITED_RETENTION_SECS: {}", value)
+                })?),
+                _ => DEFAULT_EXITED_RETENTION,
+            },
+        })
+    }
+}
+
+/// Why a session was removed by the reaper
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReapReason {
+    /// No input or output for longer than the idle timeout
+    IdleTimeout,
+    /// Alive for longer than the maximum lifetime
+    MaxLifetime,
+    /// The shell exited and the retention period passed
+    Exited,
+}
+
+impl std::fmt::Display for ReapReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::IdleTimeout => "idle timeout",
+            Self::MaxLifetime => "max lifetime",
+            Self::Exited => "exited",
+        })
+    }
+}
+
+/// Parse a non-negative integer environment variable; unset or `0` means `None`
+fn env_u64(name: &str) -> Result<Option<u64>, String> {
+    match std::env::var(name) {
+        Err(_) => Ok(None),
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => match value.parse::<u64>() {
+            Ok(0) => Ok(None),
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(format!("Invalid {}: {}", name, value)),
+        },
+    }
+}
@@ -0,0 +1,151 @@
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam};
+use rmcp::{Peer, RoleServer};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::redact::Redactor;
+
+/// Log messages buffered for each client before it misses some
+const LOG_CHANNEL_CAPACITY: usize = 256;
+
+/// A server log message, as sent to clients in `notifications/message`
+#[derive(Clone, Debug)]
+pub struct LogMessage {
+    level: LoggingLevel,
+    logger: String,
+    data: Map<String, Value>,
+}
+
+/// Server log messages for clients that asked for them with
+/// `logging/setLevel`
+#[derive(Clone)]
+pub struct LogMessages {
+    sender: broadcast::Sender<LogMessage>,
+}
+
+impl LogMessages {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(LOG_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Tracing layer publishing the server's own events
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        McpLayer {
+            sender: self.sender.clone(),
+        }
+        .with_filter(tracing_subscriber::filter::Targets::new().with_target("mcp_terminal", Level::DEBUG))
+    }
+
+    /// Send log messages at or above `level` to `peer` until it disconnects,
+    /// with secrets redacted
+    pub async fn forward(self, peer: Peer<RoleServer>, mut level: watch::Receiver<LoggingLevel>, redactor: Arc<Redactor>) {
+        let mut messages = self.sender.subscribe();
+        loop {
+            let message = match messages.recv().await {
+                Ok(message) => message,
+                // Don't log here; that would only add to the backlog
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if severity(message.level) < severity(*level.borrow_and_update()) {
+                continue;
+            }
+
+            let data = message
+                .data
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(text) => (key, Value::String(redactor.redact(&text).0)),
+                    value => (key, value),
+                })
+                .collect();
+            let param = LoggingMessageNotificationParam {
+                level: message.level,
+                logger: Some(message.logger),
+                data: Value::Object(data),
+            };
+            if peer.notify_logging_message(param).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Order of MCP log levels, from least to most severe
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// Tracing layer turning events into `LogMessage`s
+struct McpLayer {
+    sender: broadcast::Sender<LogMessage>,
+}
+
+impl<S: Subscriber> Layer<S> for McpLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Nobody listening is the common case
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            Level::ERROR => LoggingLevel::Error,
+            Level::WARN => LoggingLevel::Warning,
+            Level::INFO => LoggingLevel::Info,
+            Level::DEBUG | Level::TRACE => LoggingLevel::Debug,
+        };
+        let mut fields = FieldMap(Map::new());
+        event.record(&mut fields);
+        let _ = self.sender.send(LogMessage {
+            level,
+            logger: metadata.target().to_string(),
+            data: fields.0,
+        });
+    }
+}
+
+/// Collects the fields of an event, including its `message`
+struct FieldMap(Map<String, Value>);
+
+impl Visit for FieldMap {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::String(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
@@ -1,29 +1,149 @@
+mod artifacts;
+mod audit;
+mod auth;
+mod batch;
+mod filter;
+mod history;
+mod limits;
+mod logging;
+mod process;
+mod recording;
+mod redact;
+mod resources;
+mod risk;
 mod server;
 mod session;
+mod shell_integration;
+mod shutdown;
+mod tmux;
+mod tls;
+mod transcript;
+mod transport;
+mod truncate;
+mod watch;
 
 use rmcp::{ServiceExt, transport::stdio};
-use tracing_subscriber::EnvFilter;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing to stderr (stdout is used for MCP stdio transport)
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    // Initialize tracing to stderr (stdout is used for MCP stdio transport),
+    // and to clients that enable MCP logging
+    let log_messages = logging::LogMessages::new();
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))),
         )
-        .with_writer(std::io::stderr)
+        .with(log_messages.layer())
         .init();
 
     tracing::info!("Starting terminal-execute MCP server");
 
-    let service = server::TerminalServer::new();
-    let server = service.serve(stdio()).await.inspect_err(|e| {
-        tracing::error!("Failed to start server: {}", e);
+    let redactor = Arc::new(redact::Redactor::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?);
+    let audit = Arc::new(audit::AuditLog::from_env(Arc::clone(&redactor)).inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?);
+    let confirm_policy = risk::ConfirmPolicy::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
     })?;
+    let output_limits = truncate::OutputLimits::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let backend = session::Backend::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let transport = transport::Transport::from_args().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let shutdown_policy = shutdown::ShutdownPolicy::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let limits = limits::SessionLimits::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let recording = recording::RecordingPolicy::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let shell_integration = shell_integration::ShellIntegration::from_env().inspect_err(|e| {
+        tracing::error!("{}", e);
+    })?;
+    let session_manager = Arc::new(session::SessionManager::new(
+        backend,
+        limits,
+        recording,
+        shell_integration,
+        Arc::clone(&redactor),
+    ));
+    let restored = session_manager.reattach();
+    if restored > 0 {
+        tracing::info!("Reattached {} session(s) from a previous run", restored);
+    }
+    session_manager.start_reaper();
+
+    let service = server::TerminalServer::new(
+        Arc::clone(&session_manager),
+        Arc::clone(&audit),
+        confirm_policy,
+        output_limits,
+        log_messages,
+        redactor,
+    );
+    match transport {
+        transport::Transport::Stdio => {
+            let server = service.serve(stdio()).await.inspect_err(|e| {
+                tracing::error!("Failed to start server: {}", e);
+            })?;
+
+            // Stop serving on SIGINT/SIGTERM the same way as on client disconnect
+            let cancel = server.cancellation_token();
+            tokio::spawn(async move {
+                let signal = shutdown::wait_for_signal().await;
+                tracing::info!("Received {}", signal);
+                cancel.cancel();
+            });
+
+            tracing::info!("Server initialized, waiting for requests");
+            server.waiting().await?;
+        }
+        transport::Transport::Http(listen) => {
+            let security = transport::HttpSecurity {
+                auth: auth::Authenticator::from_env()
+                    .inspect_err(|e| {
+                        tracing::error!("{}", e);
+                    })?
+                    .map(Arc::new),
+                tls: tls::config_from_env().inspect_err(|e| {
+                    tracing::error!("{}", e);
+                })?,
+            };
+
+            // Clients come and go; only a signal stops the server
+            let cancel = CancellationToken::new();
+            let on_signal = cancel.clone();
+            tokio::spawn(async move {
+                let signal = shutdown::wait_for_signal().await;
+                tracing::info!("Received {}", signal);
+                on_signal.cancel();
+            });
 
-    tracing::info!("Server initialized, waiting for requests");
-    server.waiting().await?;
+            transport::serve_http(service, &listen, security, cancel)
+                .await
+                .inspect_err(|e| {
+                    tracing::error!("{}", e);
+                })?;
+        }
+    }
 
     tracing::info!("Server shutting down");
+    tokio::task::spawn_blocking(move || session_manager.shutdown(&shutdown_policy)).await?;
+    audit.flush();
     Ok(())
 }
@@ -0,0 +1,341 @@
+use std::time::{Duration, Instant};
+
+/// Resource usage of a process (and the children it has waited for)
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct ResourceUsage {
+    /// Wall-clock time since the process started, in milliseconds
+    pub wall_time_ms: u64,
+    /// CPU time spent in user mode, in milliseconds
+    pub user_cpu_ms: u64,
+    /// CPU time spent in kernel mode, in milliseconds
+    pub system_cpu_ms: u64,
+    /// Peak resident set size, in kilobytes
+    pub peak_rss_kb: u64,
+    /// Bytes fetched from storage (Linux only)
+    pub read_bytes: Option<u64>,
+    /// Bytes sent to storage (Linux only)
+    pub write_bytes: Option<u64>,
+}
+
+impl std::fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "wall {:.3}s, user {:.3}s, sys {:.3}s, peak RSS {} KB",
+            self.wall_time_ms as f64 / 1000.0,
+            self.user_cpu_ms as f64 / 1000.0,
+            self.system_cpu_ms as f64 / 1000.0,
+            self.peak_rss_kb
+        )?;
+        if let (Some(read), Some(written)) = (self.read_bytes, self.write_bytes) {
+            write!(f, ", read {} B, written {} B", read, written)?;
+        }
+        Ok(())
+    }
+}
+
+/// The process in a terminal's foreground
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ForegroundProcess {
+    pub pid: u32,
+    /// Executable name, e.g. `cargo`
+    pub name: String,
+    /// Full command line, e.g. `cargo test -p foo`
+    pub command: String,
+}
+
+/// Wait for a child process with timeout using polling, then reap it and
+/// collect its resource usage. Returns the exit code and usage.
+pub fn wait_with_timeout(
+    pid: u32,
+    started: Instant,
+    timeout: Duration,
+) -> Result<(u32, ResourceUsage), String> {
+    let poll_interval = Duration::from_millis(50);
+
+    loop {
+        if let Some(result) = try_wait(pid, started)? {
+            return Ok(result);
+        }
+        if started.elapsed() >= timeout {
+            return Err("Timeout".to_string());
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+/// Reap a child process if it has exited, collecting its resource usage.
+/// Returns `None` while the process is still running.
+pub fn try_wait(pid: u32, started: Instant) -> Result<Option<(u32, ResourceUsage)>, String> {
+    // On Linux, peek first without reaping so the I/O counters in /proc are
+    // still readable for the zombie
+    #[cfg(target_os = "linux")]
+    if !peek_exited(pid)? {
+        return Ok(None);
+    }
+    let io = read_io(pid);
+
+    let mut status: libc::c_int = 0;
+    // SAFETY: rusage is a plain C struct for which all-zero is a valid value
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: status and rusage are valid for writes for the duration of the call
+    let ret = unsafe { libc::wait4(pid as libc::pid_t, &mut status, libc::WNOHANG, &mut rusage) };
+    match ret {
+        0 => return Ok(None),
+        -1 => return Err(format!("Wait error: {}", std::io::Error::last_os_error())),
+        _ => {}
+    }
+
+    let exit_code = if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status) as u32
+    } else if libc::WIFSIGNALED(status) {
+        // Shell convention: 128 + signal number
+        128 + libc::WTERMSIG(status) as u32
+    } else {
+        1
+    };
+
+    let usage = ResourceUsage {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        user_cpu_ms: timeval_ms(&rusage.ru_utime),
+        system_cpu_ms: timeval_ms(&rusage.ru_stime),
+        peak_rss_kb: maxrss_kb(rusage.ru_maxrss),
+        read_bytes: io.map(|(read, _)| read),
+        write_bytes: io.map(|(_, written)| written),
+    };
+
+    Ok(Some((exit_code, usage)))
+}
+
+/// Sample the current resource usage of every process in the session led by
+/// `sid` (the shell spawned in a PTY is a session leader). Linux only.
+#[cfg(target_os = "linux")]
+pub fn session_usage(sid: u32, started: Instant) -> Option<ResourceUsage> {
+    let ticks = clock_ticks_per_sec();
+    let mut usage = ResourceUsage {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        read_bytes: Some(0),
+        write_bytes: Some(0),
+        ..Default::default()
+    };
+    let mut found = false;
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Some(stat) = read_stat(pid) else {
+            continue;
+        };
+        if stat.session != sid {
+            continue;
+        }
+        found = true;
+
+        // Include CPU time of children this process has already reaped
+        usage.user_cpu_ms += (stat.utime + stat.cutime) * 1000 / ticks;
+        usage.system_cpu_ms += (stat.stime + stat.cstime) * 1000 / ticks;
+        usage.peak_rss_kb = usage.peak_rss_kb.max(read_peak_rss_kb(pid).unwrap_or(0));
+        if let Some((read, written)) = read_io(pid) {
+            usage.read_bytes = usage.read_bytes.map(|b| b + read);
+            usage.write_bytes = usage.write_bytes.map(|b| b + written);
+        }
+    }
+
+    found.then_some(usage)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn session_usage(_sid: u32, _started: Instant) -> Option<ResourceUsage> {
+    None
+}
+
+/// Send `signal` to every process in the session led by `sid`. Foreground
+/// jobs run in their own process groups, so signalling the shell's group
+/// alone would miss them.
+#[cfg(target_os = "linux")]
+pub fn signal_session(sid: u32, signal: libc::c_int) {
+    for pid in session_members(sid) {
+        // SAFETY: kill has no memory safety preconditions
+        unsafe { libc::kill(pid as libc::pid_t, signal) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn signal_session(sid: u32, signal: libc::c_int) {
+    // SAFETY: killpg has no memory safety preconditions
+    unsafe { libc::killpg(sid as libc::pid_t, signal) };
+}
+
+/// Whether any process in the session led by `sid` is still running
+#[cfg(target_os = "linux")]
+pub fn session_alive(sid: u32) -> bool {
+    !session_members(sid).is_empty()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn session_alive(sid: u32) -> bool {
+    // SAFETY: kill with signal 0 only checks for existence
+    unsafe { libc::kill(-(sid as libc::pid_t), 0) == 0 }
+}
+
+/// Foreground process group of the terminal controlling `pid`. Linux only;
+/// PTYs owned by this process use `tcgetpgrp` on the master instead.
+#[cfg(target_os = "linux")]
+pub fn foreground_group(pid: u32) -> Option<u32> {
+    read_stat(pid)?.tpgid
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn foreground_group(_pid: u32) -> Option<u32> {
+    None
+}
+
+/// Name and command line of the leader of process group `pgid`. Linux only.
+#[cfg(target_os = "linux")]
+pub fn foreground_process(pgid: u32) -> Option<ForegroundProcess> {
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pgid)).ok()?;
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pgid)).ok()?;
+    let command = cmdline
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(ForegroundProcess {
+        pid: pgid,
+        name: name.trim_end().to_string(),
+        command,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn foreground_process(_pgid: u32) -> Option<ForegroundProcess> {
+    None
+}
+
+/// Current working directory of a process. Linux only.
+#[cfg(target_os = "linux")]
+pub fn cwd(pid: u32) -> Option<String> {
+    let path = std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cwd(_pid: u32) -> Option<String> {
+    None
+}
+
+/// PIDs of the running (non-zombie) processes in the session led by `sid`
+#[cfg(target_os = "linux")]
+fn session_members(sid: u32) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| read_stat(pid).is_some_and(|stat| stat.session == sid && !stat.zombie))
+        .collect()
+}
+
+/// Check whether a child has exited without reaping it
+#[cfg(target_os = "linux")]
+fn peek_exited(pid: u32) -> Result<bool, String> {
+    // SAFETY: siginfo_t is a plain C struct for which all-zero is a valid value
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: info is valid for writes for the duration of the call
+    let ret = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if ret == -1 {
+        return Err(format!("Wait error: {}", std::io::Error::last_os_error()));
+    }
+    // SAFETY: si_pid is populated by waitid for WEXITED; zero means still running
+    Ok(unsafe { info.si_pid() } != 0)
+}
+
+/// Selected fields from /proc/<pid>/stat
+#[cfg(target_os = "linux")]
+struct ProcStat {
+    /// Exited but not yet reaped
+    zombie: bool,
+    session: u32,
+    /// Foreground process group of the controlling terminal, if it has one
+    tpgid: Option<u32>,
+    utime: u64,
+    stime: u64,
+    cutime: u64,
+    cstime: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name is parenthesized and may contain spaces — skip past it
+    let rest = &stat[stat.rfind(')')? + 2..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // Field numbers from proc(5), offset by the two skipped fields (pid, comm)
+    let field = |n: usize| fields.get(n - 3).and_then(|s| s.parse::<u64>().ok());
+    Some(ProcStat {
+        zombie: fields.first() == Some(&"Z"),
+        session: field(6)? as u32,
+        // -1 without a controlling terminal
+        tpgid: field(8).map(|pgid| pgid as u32),
+        utime: field(14)?,
+        stime: field(15)?,
+        cutime: field(16)?,
+        cstime: field(17)?,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn read_peak_rss_kb(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+/// Storage I/O counters (read_bytes, write_bytes) from /proc/<pid>/io
+#[cfg(target_os = "linux")]
+fn read_io(pid: u32) -> Option<(u64, u64)> {
+    let io = std::fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    let get = |key: &str| {
+        io.lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|v| v.trim().parse::<u64>().ok())
+    };
+    Some((get("read_bytes:")?, get("write_bytes:")?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_io(_pid: u32) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn clock_ticks_per_sec() -> u64 {
+    // SAFETY: sysconf has no memory safety preconditions
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as u64 } else { 100 }
+}
+
+fn timeval_ms(tv: &libc::timeval) -> u64 {
+    tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000
+}
+
+/// ru_maxrss is in kilobytes on Linux but in bytes on macOS
+fn maxrss_kb(maxrss: libc::c_long) -> u64 {
+    if cfg!(target_os = "macos") {
+        maxrss as u64 / 1024
+    } else {
+        maxrss as u64
+    }
+}
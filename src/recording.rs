@@ -0,0 +1,243 @@
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::redact::Redactor;
+use crate::tmux;
+
+/// Largest recording or transcript returned inline rather than requiring
+/// a path to write it to
+pub const MAX_INLINE_EXPORT: u64 = 1024 * 1024;
+
+/// Where session recordings are written and whether sessions are recorded
+/// unless `create_session` says otherwise
+#[derive(Clone, Debug)]
+pub struct RecordingPolicy {
+    pub dir: PathBuf,
+    pub record_by_default: bool,
+}
+
+impl RecordingPolicy {
+    /// Read the policy from `MCP_TERMINAL_RECORDING_DIR` (default
+    /// `recordings` in the state directory) and `MCP_TERMINAL_RECORD`
+    /// (`1`/`true` to record every session)
+    pub fn from_env() -> Result<Self, String> {
+        let dir = match std::env::var("MCP_TERMINAL_RECORDING_DIR") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => tmux::state_dir()?.join("recordings"),
+        };
+        let record_by_default = match std::env::var("MCP_TERMINAL_RECORD").as_deref() {
+            Err(_) | Ok("") | Ok("0") | Ok("false") => false,
+            Ok("1") | Ok("true") => true,
+            Ok(other) => return Err(format!("Invalid MCP_TERMINAL_RECORD: {}", other)),
+        };
+        Ok(Self {
+            dir,
+            record_by_default,
+        })
+    }
+
+    /// Path of a session's recording
+    pub fn path(&self, session_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.cast", session_id))
+    }
+}
+
+/// Who may export a recording. Kept next to it in `<session_id>.json`, so it
+/// can be checked after the session is gone.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct RecordingOwner {
+    /// Client that created the session
+    pub owner: Option<String>,
+    pub project: Option<String>,
+}
+
+/// A session's asciicast v2 recording. The file is created the first time
+/// recording is enabled; disabling it only pauses writing.
+pub struct Recording {
+    path: PathBuf,
+    width: u16,
+    height: u16,
+    title: String,
+    owner: RecordingOwner,
+    file: Option<File>,
+    enabled: bool,
+    /// Time zero of the recording's event timestamps
+    started: Instant,
+    /// Trailing bytes of an incomplete UTF-8 sequence in the last output chunk
+    carry: Vec<u8>,
+}
+
+impl Recording {
+    pub fn new(path: PathBuf, width: u16, height: u16, title: String, owner: RecordingOwner) -> Self {
+        Self {
+            path,
+            width,
+            height,
+            title,
+            owner,
+            file: None,
+            enabled: false,
+            started: Instant::now(),
+            carry: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start or pause recording
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && self.file.is_none() {
+            self.file = Some(self.open()?);
+        }
+        self.enabled = enabled;
+        Ok(())
+    }
+
+    /// Open the recording file, writing the header to a new file. An
+    /// existing file (from before a server restart) is continued after its
+    /// last event.
+    fn open(&mut self) -> Result<File, String> {
+        let fail = |e: std::io::Error| format!("Failed to open recording {}: {}", self.path.display(), e);
+        if let Some(dir) = self.path.parent() {
+            // Recordings and their owner files are only for us
+            DirBuilder::new().recursive(true).mode(0o700).create(dir).map_err(fail)?;
+        }
+        if let Some(last) = last_event_time(&self.path) {
+            self.started = Instant::now() - last;
+            return OpenOptions::new()
+                .append(true)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(&self.path)
+                .map_err(fail);
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&self.path)
+            .map_err(fail)?;
+        let header = serde_json::json!({
+            "version": 2,
+            "width": self.width,
+            "height": self.height,
+            "timestamp": chrono::Utc::now().timestamp(),
+            "title": self.title,
+            "env": { "TERM": "xterm-256color" },
+        });
+        writeln!(file, "{}", header).map_err(fail)?;
+        let owner = serde_json::to_string(&self.owner).unwrap_or_default();
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(owner_path(&self.path))
+            .and_then(|mut f| f.write_all(owner.as_bytes()))
+            .map_err(fail)?;
+        self.started = Instant::now();
+        Ok(file)
+    }
+
+    /// Record output from the terminal
+    pub fn output(&mut self, data: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        self.carry.extend_from_slice(data);
+        // Hold back a multi-byte character split across chunks
+        let complete = match std::str::from_utf8(&self.carry) {
+            Ok(_) => self.carry.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.carry.len(),
+        };
+        let text = String::from_utf8_lossy(&self.carry[..complete]).into_owned();
+        self.carry.drain(..complete);
+        if !text.is_empty() {
+            self.event("o", &text);
+        }
+    }
+
+    /// Record input sent to the terminal
+    pub fn input(&mut self, text: &str) {
+        if self.enabled {
+            self.event("i", text);
+        }
+    }
+
+    fn event(&mut self, kind: &str, text: &str) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let time = self.started.elapsed().as_secs_f64();
+        // One write per event, so a crash can only cut off the last line
+        let line = format!("{}\n", serde_json::json!([(time * 1e6).round() / 1e6, kind, text]));
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::warn!(path = %self.path.display(), "Failed to write recording, stopping it: {}", e);
+            self.enabled = false;
+        }
+    }
+}
+
+/// Path of the owner file kept next to the recording at `path`
+fn owner_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+/// Open a file in the recording directory for reading, refusing a symlink
+/// put in its place
+fn open_read(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW).open(path)
+}
+
+/// Owner and project of the recording at `path`, if they were kept
+pub fn owner(path: &Path) -> Option<RecordingOwner> {
+    let mut content = String::new();
+    open_read(&owner_path(path)).ok()?.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Time of the last event in an existing recording
+fn last_event_time(path: &Path) -> Option<Duration> {
+    let file = open_read(path).ok()?;
+    let last = BufReader::new(file).lines().map_while(Result::ok).last()?;
+    let event: serde_json::Value = serde_json::from_str(&last).ok()?;
+    let time = event.get(0).and_then(|t| t.as_f64())?;
+    Some(Duration::from_secs_f64(time))
+}
+
+/// A recording with the text of every event redacted
+pub fn export(path: &Path, redactor: &Redactor) -> Result<String, String> {
+    let fail = |e: std::io::Error| format!("Failed to read recording {}: {}", path.display(), e);
+    let mut content = String::new();
+    open_read(path).and_then(|mut f| f.read_to_string(&mut content)).map_err(fail)?;
+    let mut lines = content.lines();
+    let mut exported = String::with_capacity(content.len());
+    if let Some(header) = lines.next() {
+        exported.push_str(header);
+        exported.push('\n');
+    }
+    for line in lines {
+        let mut event: serde_json::Value = match serde_json::from_str(line) {
+            Ok(event) => event,
+            // A write cut short by a crash
+            Err(_) => continue,
+        };
+        if let Some(text) = event.get_mut(2) {
+            if let Some(s) = text.as_str() {
+                *text = serde_json::Value::String(redactor.redact(s).0);
+            }
+        }
+        exported.push_str(&event.to_string());
+        exported.push('\n');
+    }
+    Ok(exported)
+}
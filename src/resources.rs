@@ -0,0 +1,101 @@
+use rmcp::model::{AnnotateAble, RawResource, RawResourceTemplate, Resource, ResourceTemplate};
+
+use crate::session::SessionInfo;
+
+const URI_PREFIX: &str = "terminal://session/";
+
+/// A view of a session exposed as an MCP resource
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    /// The visible terminal screen, as rendered by a terminal emulator
+    Screen,
+    /// Recent output, including output already returned by `read_output`
+    Scrollback,
+    /// Session status as JSON, as returned by `list_sessions`
+    Info,
+}
+
+impl View {
+    const ALL: [View; 3] = [View::Screen, View::Scrollback, View::Info];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Screen => "screen",
+            Self::Scrollback => "scrollback",
+            Self::Info => "info",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Screen => "Visible screen of the terminal session",
+            Self::Scrollback => "Recent output of the terminal session, ANSI escapes stripped",
+            Self::Info => "Status of the terminal session",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Screen | Self::Scrollback => "text/plain",
+            Self::Info => "application/json",
+        }
+    }
+}
+
+/// URI of a session resource
+pub fn uri(session_id: &str, view: View) -> String {
+    format!("{}{}/{}", URI_PREFIX, session_id, view.name())
+}
+
+/// Split a session resource URI into the session ID and view
+pub fn parse(uri: &str) -> Result<(&str, View), String> {
+    let invalid = || format!("Unknown resource {}", uri);
+    let (session_id, name) = uri
+        .strip_prefix(URI_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+        .ok_or_else(invalid)?;
+    let view = View::ALL
+        .into_iter()
+        .find(|v| v.name() == name)
+        .ok_or_else(invalid)?;
+    if session_id.is_empty() || session_id.contains('/') {
+        return Err(invalid());
+    }
+    Ok((session_id, view))
+}
+
+/// Resources of a session. Reaped sessions only have their info.
+pub fn for_session(info: &SessionInfo) -> Vec<Resource> {
+    let label = match &info.project {
+        Some(project) => format!("{} ({})", info.session_id, project),
+        None => info.session_id.clone(),
+    };
+    View::ALL
+        .into_iter()
+        .filter(|view| info.reap_reason.is_none() || *view == View::Info)
+        .map(|view| {
+            let mut resource = RawResource::new(uri(&info.session_id, view), format!("{} {}", label, view.name()));
+            resource.description = Some(view.description().to_string());
+            resource.mime_type = Some(view.mime_type().to_string());
+            resource.no_annotation()
+        })
+        .collect()
+}
+
+/// URI templates of the session resources
+pub fn templates() -> Vec<ResourceTemplate> {
+    View::ALL
+        .into_iter()
+        .map(|view| {
+            RawResourceTemplate {
+                uri_template: format!("{}{{session_id}}/{}", URI_PREFIX, view.name()),
+                name: format!("session {}", view.name()),
+                title: None,
+                description: Some(view.description().to_string()),
+                mime_type: Some(view.mime_type().to_string()),
+                icons: None,
+            }
+            .no_annotation()
+        })
+        .collect()
+}
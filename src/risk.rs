@@ -0,0 +1,306 @@
+/// Policy for confirming risky commands with the user via MCP elicitation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfirmPolicy {
+    /// Never ask
+    Off,
+    /// Ask when the client supports elicitation, otherwise run anyway
+    Ask,
+    /// Ask, and reject the command when the client cannot be asked
+    Require,
+}
+
+impl ConfirmPolicy {
+    /// Read the policy from `MCP_TERMINAL_CONFIRM` (`off`, `ask` or `require`).
+    /// Defaults to `ask`.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("MCP_TERMINAL_CONFIRM").as_deref() {
+            Err(_) | Ok("") | Ok("ask") => Ok(Self::Ask),
+            Ok("off") => Ok(Self::Off),
+            Ok("require") => Ok(Self::Require),
+            Ok(other) => Err(format!("Invalid MCP_TERMINAL_CONFIRM: {}", other)),
+        }
+    }
+}
+
+/// A part of a command line flagged by the risk classifier
+#[derive(Clone, Debug)]
+pub struct Risk {
+    /// The simple command that was flagged, e.g. "git push --force origin main"
+    pub command: String,
+    /// Why it was flagged
+    pub reason: &'static str,
+}
+
+/// Classify a shell command line, returning every simple command in it that
+/// should be confirmed by a human before running
+pub fn classify(command_line: &str) -> Vec<Risk> {
+    let mut risks = Vec::new();
+
+    for words in split_commands(command_line) {
+        let words = strip_env_assignments(&words);
+        let Some(program) = words.first() else {
+            continue;
+        };
+        if let Some(reason) = classify_words(program, &words[1..]) {
+            risks.push(Risk {
+                command: words.join(" "),
+                reason,
+            });
+        }
+    }
+
+    // SQL statements can hide anywhere in arguments, heredocs or quoted strings
+    let upper = command_line.to_ascii_uppercase();
+    let upper = upper.split_whitespace().collect::<Vec<_>>().join(" ");
+    for (statement, reason) in [
+        ("DROP TABLE", "drops a database table"),
+        ("DROP DATABASE", "drops a database"),
+        ("DROP SCHEMA", "drops a database schema"),
+        ("TRUNCATE TABLE", "truncates a database table"),
+    ] {
+        if upper.contains(statement) {
+            risks.push(Risk {
+                command: command_line.trim().to_string(),
+                reason,
+            });
+        }
+    }
+
+    risks
+}
+
+fn classify_words(program: &str, args: &[String]) -> Option<&'static str> {
+    let program = program.rsplit('/').next().unwrap_or(program);
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    // Short flags may be combined, e.g. "-rf"
+    let has_short = |c: char| {
+        args.iter()
+            .any(|a| a.starts_with('-') && !a.starts_with("--") && a.contains(c))
+    };
+    let subcommand = args.first().map(String::as_str);
+
+    match program {
+        "sudo" | "doas" | "su" | "pkexec" => Some("runs with elevated privileges"),
+        "rm" if has_short('r') || has_short('R') || has("--recursive") => {
+            Some("recursively deletes files")
+        }
+        "rm" if has_short('f') || has("--force") => Some("force-deletes files"),
+        "mkfs" | "wipefs" | "shred" => Some("destroys data on a device or file"),
+        _ if program.starts_with("mkfs.") => Some("destroys data on a device or file"),
+        "dd" if args.iter().any(|a| a.starts_with("of=/dev/")) => {
+            Some("writes directly to a device")
+        }
+        "git" => classify_git(args),
+        "cargo" | "npm" | "yarn" | "pnpm" | "poetry" if subcommand == Some("publish") => {
+            Some("publishes a package")
+        }
+        "twine" if subcommand == Some("upload") => Some("publishes a package"),
+        "gem" if subcommand == Some("push") => Some("publishes a package"),
+        _ => None,
+    }
+}
+
+fn classify_git(args: &[String]) -> Option<&'static str> {
+    // Skip global options like "-C <dir>" to find the subcommand
+    let mut rest = args.iter().map(String::as_str);
+    let subcommand = loop {
+        match rest.next()? {
+            "-C" | "-c" | "--git-dir" | "--work-tree" => {
+                rest.next();
+            }
+            arg if arg.starts_with('-') => {}
+            arg => break arg,
+        }
+    };
+    let rest: Vec<&str> = rest.collect();
+
+    match subcommand {
+        "push"
+            if rest.iter().any(|a| {
+                matches!(*a, "-f" | "--force" | "--mirror" | "--delete" | "-d")
+                    || a.starts_with("--force-with-lease")
+                    || a.starts_with('+')
+            }) =>
+        {
+            Some("force-pushes or deletes remote refs")
+        }
+        "reset" if rest.contains(&"--hard") => Some("discards uncommitted changes"),
+        "clean" if rest.iter().any(|a| a.starts_with('-') && a.contains('f')) => {
+            Some("deletes untracked files")
+        }
+        _ => None,
+    }
+}
+
+/// Drop leading `NAME=value` assignments from a simple command
+fn strip_env_assignments(words: &[String]) -> Vec<String> {
+    words
+        .iter()
+        .skip_while(|w| {
+            w.split_once('=').is_some_and(|(name, _)| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Split a command line into simple commands (on `;`, `&&`, `||`, `|`, `&`,
+/// newlines and subshell parentheses), each as a list of unquoted words.
+/// This is a best-effort tokenizer, not a full shell parser.
+fn split_commands(command_line: &str) -> Vec<Vec<String>> {
+    let mut commands = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command_line.chars().peekable();
+
+    let end_word = |word: &mut String, in_word: &mut bool, words: &mut Vec<String>| {
+        if *in_word {
+            words.push(std::mem::take(word));
+            *in_word = false;
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    word.push(c);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(next) = chars.next() {
+                                word.push(next);
+                            }
+                        }
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            ';' | '&' | '|' | '\n' | '(' | ')' | '`' => {
+                end_word(&mut word, &mut in_word, &mut words);
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            c if c.is_whitespace() => end_word(&mut word, &mut in_word, &mut words),
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    end_word(&mut word, &mut in_word, &mut words);
+    if !words.is_empty() {
+        commands.push(words);
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reasons `command_line` was flagged for
+    fn reasons(command_line: &str) -> Vec<&'static str> {
+        classify(command_line).into_iter().map(|risk| risk.reason).collect()
+    }
+
+    #[test]
+    fn flags_each_risk_class() {
+        let cases = [
+            ("sudo apt install foo", "runs with elevated privileges"),
+            ("doas reboot", "runs with elevated privileges"),
+            ("rm -rf build", "recursively deletes files"),
+            ("rm -R -v dir", "recursively deletes files"),
+            ("rm --recursive dir", "recursively deletes files"),
+            ("rm -f file", "force-deletes files"),
+            ("mkfs.ext4 /dev/sdb1", "destroys data on a device or file"),
+            ("shred secrets.txt", "destroys data on a device or file"),
+            ("dd if=image.iso of=/dev/sdb bs=4M", "writes directly to a device"),
+            ("git push --force origin main", "force-pushes or deletes remote refs"),
+            ("git push origin +main", "force-pushes or deletes remote refs"),
+            ("git push --force-with-lease=main origin", "force-pushes or deletes remote refs"),
+            ("git push origin --delete feature", "force-pushes or deletes remote refs"),
+            ("git -C repo push -f", "force-pushes or deletes remote refs"),
+            ("git reset --hard HEAD~1", "discards uncommitted changes"),
+            ("git clean -fdx", "deletes untracked files"),
+            ("cargo publish", "publishes a package"),
+            ("npm publish --access public", "publishes a package"),
+            ("twine upload dist/*", "publishes a package"),
+            ("gem push foo.gem", "publishes a package"),
+            ("psql -c 'drop   table users'", "drops a database table"),
+            ("mysql -e \"DROP DATABASE prod\"", "drops a database"),
+            ("psql -c 'DROP SCHEMA app CASCADE'", "drops a database schema"),
+            ("psql -c 'TRUNCATE TABLE events'", "truncates a database table"),
+        ];
+        for (command_line, reason) in cases {
+            assert_eq!(reasons(command_line), [reason], "{command_line}");
+        }
+    }
+
+    #[test]
+    fn leaves_safe_commands_alone() {
+        let safe = [
+            "ls -la",
+            "rm file.txt",
+            "rm -i file.txt",
+            "git push origin main",
+            "git reset --soft HEAD~1",
+            "git clean -n",
+            "git log --format=-f",
+            "dd if=/dev/zero of=disk.img bs=1M count=10",
+            "cargo build --release",
+            "npm install",
+            "echo 'sudo rm -rf /'",
+            "grep -r sudo .",
+            "",
+        ];
+        for command_line in safe {
+            assert_eq!(reasons(command_line), Vec::<&str>::new(), "{command_line}");
+        }
+    }
+
+    #[test]
+    fn finds_risky_commands_in_compound_lines() {
+        let risks = classify("cd /tmp && FOO=1 sudo ls | tee out; (rm -rf x)");
+        let commands: Vec<&str> = risks.iter().map(|risk| risk.command.as_str()).collect();
+        assert_eq!(commands, ["sudo ls", "rm -rf x"]);
+    }
+
+    #[test]
+    fn resolves_program_paths() {
+        assert_eq!(reasons("/usr/bin/sudo id"), ["runs with elevated privileges"]);
+    }
+
+    #[test]
+    fn splits_on_operators_and_quotes() {
+        assert_eq!(
+            split_commands(r#"a "b c"&&d 'e;f'|g\ h"#),
+            [vec!["a", "b c"], vec!["d", "e;f"], vec!["g h"]]
+        );
+    }
+
+    #[test]
+    fn strips_leading_assignments_only() {
+        let words: Vec<String> = ["A=1", "B_2=x", "cmd", "C=3"].map(String::from).to_vec();
+        assert_eq!(strip_env_assignments(&words), ["cmd", "C=3"]);
+    }
+}
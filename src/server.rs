@@ -1,31 +1,457 @@
+use chrono::Utc;
 use rmcp::{
-    ServerHandler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    schemars, tool, tool_handler, tool_router,
+    Peer, RoleServer, ServerHandler,
+    handler::server::{router::prompt::PromptRouter, router::tool::ToolRouter, wrapper::Parameters},
+    model::{
+        CustomNotification, Extensions, GetPromptRequestParams, GetPromptResult, ListPromptsResult, ListResourceTemplatesResult,
+        ListResourcesResult, LoggingLevel, PaginatedRequestParams, PromptMessage, PromptMessageRole, ReadResourceRequestParams,
+        ReadResourceResult, ResourceContents, ResourceUpdatedNotificationParam, ServerCapabilities, ServerInfo,
+        ServerNotification, SetLevelRequestParams, SubscribeRequestParams, UnsubscribeRequestParams,
+    },
+    prompt, prompt_handler, prompt_router, schemars,
+    service::{ElicitationError, ElicitationMode, NotificationContext, RequestContext},
+    tool, tool_handler, tool_router, ErrorData as McpError,
 };
+use axum::http::request::Parts;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
 
-use crate::session::SessionManager;
+use crate::artifacts::ArtifactStore;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::{Client, Scope};
+use crate::batch::{self, Status};
+use crate::filter::{self, Selector, SessionFilter, SortKey};
+use crate::logging::LogMessages;
+use crate::redact::{self, Redactor};
+use crate::resources::{self, View};
+use crate::risk::{self, ConfirmPolicy};
+use crate::session::{NewSession, SessionEventKind, SessionManager};
+use crate::transcript;
+use crate::transport::ConnectionId;
+use crate::truncate::{self, OutputLimits};
+use crate::watch::WatchAction;
 
 #[derive(Clone)]
 pub struct TerminalServer {
     session_manager: Arc<SessionManager>,
+    audit: Arc<AuditLog>,
+    confirm_policy: ConfirmPolicy,
+    /// Default limits on the output of `execute`
+    output_limits: OutputLimits,
+    /// Full outputs of truncated `execute` results, shared by all connections
+    artifacts: Arc<ArtifactStore>,
+    log_messages: LogMessages,
+    redactor: Arc<Redactor>,
+    /// State of the connection this server handles
+    connection: Arc<Connection>,
     tool_router: ToolRouter<Self>,
+    prompt_router: PromptRouter<Self>,
+}
+
+/// Time to collect further output before notifying a subscriber
+const UPDATE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Method of the notification sent for session events
+const SESSION_EVENT_METHOD: &str = "notifications/terminal/session";
+
+/// Lines of scrollback included in prompts
+const PROMPT_SCROLLBACK_LINES: usize = 200;
+
+/// Commands `session_history` lists unless given a limit
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// Lines `read_artifact` returns unless given a limit
+const DEFAULT_ARTIFACT_LINES: usize = 200;
+
+/// Tasks notifying a connection of resource updates and log messages.
+/// Stopped when the connection closes.
+#[derive(Default)]
+struct Connection {
+    /// Resource subscriptions, keyed by URI
+    subscriptions: Mutex<HashMap<String, AbortHandle>>,
+    /// Minimum level of log messages to send, once the client set one
+    logging: Mutex<Option<(watch::Sender<LoggingLevel>, AbortHandle)>>,
+    /// Session event notifications
+    session_events: Mutex<Option<AbortHandle>>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for task in self.subscriptions.lock().unwrap().values() {
+            task.abort();
+        }
+        if let Some((_, task)) = self.logging.lock().unwrap().as_ref() {
+            task.abort();
+        }
+        if let Some(task) = self.session_events.lock().unwrap().as_ref() {
+            task.abort();
+        }
+    }
 }
 
 impl TerminalServer {
-    pub fn new() -> Self {
+    pub fn new(
+        session_manager: Arc<SessionManager>,
+        audit: Arc<AuditLog>,
+        confirm_policy: ConfirmPolicy,
+        output_limits: OutputLimits,
+        log_messages: LogMessages,
+        redactor: Arc<Redactor>,
+    ) -> Self {
         Self {
-            session_manager: Arc::new(SessionManager::new()),
+            session_manager,
+            audit,
+            confirm_policy,
+            output_limits,
+            artifacts: Arc::default(),
+            log_messages,
+            redactor,
+            connection: Arc::default(),
             tool_router: Self::tool_router(),
+            prompt_router: Self::prompt_router(),
+        }
+    }
+
+    /// A server for a new connection, with its own subscriptions
+    pub fn for_connection(&self) -> Self {
+        Self {
+            connection: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Session ID that a `session_id` argument, given as an ID or a name,
+    /// refers to
+    fn resolve(&self, caller: &Caller, session_id: &str) -> String {
+        self.session_manager.resolve(session_id, caller.owner.as_deref())
+    }
+
+    /// Like `resolve`, for tools that change the session: names only refer
+    /// to the caller's own sessions
+    fn resolve_own(&self, caller: &Caller, session_id: &str) -> String {
+        self.session_manager.resolve_own(session_id, caller.owner.as_deref())
+    }
+
+    /// Check that the caller may read a session resource
+    fn authorize_resource(&self, caller: &Caller, session_id: &str, view: View) -> Result<(), String> {
+        if view != View::Info {
+            return self.authorize_session(caller, Scope::Observe, session_id);
+        }
+        // Reaped sessions keep their info, so check against it
+        let info = self.session_manager.session_info(session_id, caller.owner.as_deref())?;
+        match &caller.client {
+            Some(client) => client.authorize(Scope::Observe, info.project.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    /// Status and recent output of a session, formatted as prompt context
+    fn session_context(&self, caller: &Caller, session_id: &str) -> Result<String, McpError> {
+        let session_id = &self.resolve(caller, session_id);
+        self.authorize_session(caller, Scope::Observe, session_id)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let info = self
+            .session_manager
+            .session_info(session_id, caller.owner.as_deref())
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let scrollback = self
+            .session_manager
+            .scrollback(session_id)
+            .unwrap_or_default();
+        let lines: Vec<&str> = scrollback.lines().collect();
+        let tail = lines[lines.len().saturating_sub(PROMPT_SCROLLBACK_LINES)..].join("\n");
+
+        let status = match (info.is_alive, info.exit_code) {
+            (true, _) => match &info.foreground {
+                Some(foreground) => format!("running, foreground: {}", foreground.command),
+                None => "running".to_string(),
+            },
+            (false, Some(code)) => format!("exited with code {}", code),
+            (false, None) => "exited".to_string(),
+        };
+        // Exit codes of the commands run in the shell, with shell integration
+        let mut commands = String::new();
+        if let Some(code) = info.shell.as_ref().and_then(|s| s.last_exit_code) {
+            commands.push_str(&format!("\nLast command exit code: {}", code));
+        }
+        let history = self
+            .session_manager
+            .history(session_id, None, usize::MAX)
+            .unwrap_or_default();
+        if let Some(failed) = history.iter().rev().find(|c| c.exit_code.is_some_and(|code| code != 0)) {
+            commands.push_str(&format!(
+                "\nLast failed command: `{}` (command_id {}, exit code {}); see session_history for its full output",
+                failed.input.trim_end(),
+                failed.command_id,
+                failed.exit_code.unwrap_or_default()
+            ));
+        }
+        Ok(format!(
+            "Terminal session {} (cwd: {}, project: {}, status: {}).{}\n\nRecent output:\n```\n{}\n```",
+            session_id,
+            info.current_cwd.as_ref().unwrap_or(&info.cwd),
+            info.project.as_deref().unwrap_or("none"),
+            status,
+            commands,
+            tail
+        ))
+    }
+
+    /// Send `notifications/terminal/session` for the events of sessions the
+    /// caller may observe, until the connection closes
+    async fn forward_session_events(
+        session_manager: Arc<SessionManager>,
+        redactor: Arc<Redactor>,
+        caller: Caller,
+        peer: Peer<RoleServer>,
+    ) {
+        let mut events = session_manager.subscribe_events();
+        loop {
+            let mut event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "Dropped session events for a slow client");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Ok(info) = session_manager.session_info(&event.session_id, caller.owner.as_deref()) else {
+                continue;
+            };
+            if caller.client.as_ref().is_some_and(|c| !c.allows_project(info.project.as_deref())) {
+                continue;
+            }
+            if let SessionEventKind::OutputMatched { line, .. } = &mut event.kind {
+                *line = redactor.redact(line).0;
+            }
+
+            let params = serde_json::to_value(&event).ok();
+            let notification = CustomNotification::new(SESSION_EVENT_METHOD, params);
+            if peer
+                .send_notification(ServerNotification::CustomNotification(notification))
+                .await
+                .is_err()
+            {
+                break;
+            }
         }
     }
+
+    /// Current contents of a session resource
+    fn read_view(&self, caller: &Caller, session_id: &str, view: View) -> Result<String, String> {
+        match view {
+            View::Screen => self.session_manager.screen(session_id),
+            View::Scrollback => self.session_manager.scrollback(session_id),
+            View::Info => {
+                let info = self.session_manager.session_info(session_id, caller.owner.as_deref())?;
+                serde_json::to_string_pretty(&info).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Ask the user to approve the commands flagged by the risk classifier,
+    /// all in one request. Each command runs in its own shell, so each is
+    /// classified on its own. Returns an error if they must not run.
+    async fn confirm_commands(&self, peer: &Peer<RoleServer>, commands: &[String], cwd: &str) -> Result<(), String> {
+        let risks: Vec<risk::Risk> = commands.iter().flat_map(|c| risk::classify(c)).collect();
+        if risks.is_empty() || self.confirm_policy == ConfirmPolicy::Off {
+            return Ok(());
+        }
+        let command = commands.join("\n");
+
+        let reasons = risks.iter().map(|r| r.reason).collect::<Vec<_>>().join(", ");
+
+        if !peer.supported_elicitation_modes().contains(&ElicitationMode::Form) {
+            if self.confirm_policy == ConfirmPolicy::Require {
+                tracing::warn!(command = %command, reasons = %reasons, "Rejected risky command: client does not support elicitation");
+                return Err(format!(
+                    "Command requires user confirmation ({}), but the client does not support elicitation",
+                    reasons
+                ));
+            }
+            tracing::warn!(command = %command, reasons = %reasons, "Running risky command without confirmation: client does not support elicitation");
+            return Ok(());
+        }
+
+        let flagged = risks
+            .iter()
+            .map(|r| format!("- `{}` — {}", r.command, r.reason))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let message = format!(
+            "An agent wants to run a potentially dangerous command.\n\n\
+             Command: {}\nWorking directory: {}\n\nFlagged:\n{}\n\nApprove?",
+            command, cwd, flagged
+        );
+
+        match peer.elicit::<Confirmation>(message).await {
+            Ok(Some(Confirmation { approve: true })) => Ok(()),
+            Ok(_) | Err(ElicitationError::UserDeclined) | Err(ElicitationError::UserCancelled) => {
+                tracing::warn!(command = %command, reasons = %reasons, "Risky command rejected by user");
+                Err(format!("Command rejected by user ({})", reasons))
+            }
+            Err(e) => {
+                tracing::warn!(command = %command, error = %e, "Failed to confirm risky command");
+                Err(format!("Failed to get user confirmation: {}", e))
+            }
+        }
+    }
+
+    /// Check that the caller holds `scope`, may use the session's project,
+    /// and owns the session or has it shared with them. Unknown sessions
+    /// pass, so the tool reports them as not found.
+    fn authorize_session(&self, caller: &Caller, scope: Scope, session_id: &str) -> Result<(), String> {
+        if let Some(client) = &caller.client {
+            match self.session_manager.session_project(session_id) {
+                Some(project) => client.authorize(scope, project.as_deref())?,
+                None => client.check_scope(scope)?,
+            }
+        }
+        self.session_manager.check_access(session_id, caller.owner.as_deref())
+    }
+
+    /// Create a session for `caller` and audit it as a call to `tool`
+    fn create(
+        &self,
+        tool: &'static str,
+        params: CreateSessionParams,
+        caller: &Caller,
+        peer: &Peer<RoleServer>,
+    ) -> Result<String, String> {
+        let started = Instant::now();
+        let mut record = self.audit_record(tool, peer, caller.client.as_deref());
+        record.command = params.shell.clone();
+
+        let authorized = match &caller.client {
+            Some(client) => client.authorize(Scope::Sessions, params.project.as_deref()),
+            None => Ok(()),
+        };
+        let result = authorized.and_then(|()| {
+            self.session_manager.create_session(NewSession {
+                cwd: params.cwd,
+                shell: params.shell,
+                project: params.project,
+                owner: caller.owner.clone(),
+                name: params.name,
+                labels: params.labels.unwrap_or_default(),
+                description: params.description,
+                watch: params.watch.unwrap_or_default(),
+                record: params.record,
+            })
+        });
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(session_id) => {
+                record.cwd = self.session_manager.session_cwd(session_id);
+                record.session_id = Some(session_id.clone());
+            }
+            Err(e) => record.error = Some(e.clone()),
+        }
+        self.audit.record(record);
+        result
+    }
+
+    /// Fit a command's output into `limits`. If anything was left out, the
+    /// output is kept whole as an artifact of the caller's (bar the middle
+    /// of very long output, which `execute` drops with a marker), and a
+    /// summary of what was left out is returned with the artifact's ID.
+    fn fit_output(
+        &self,
+        caller: &Caller,
+        command: &str,
+        output: String,
+        limits: &OutputLimits,
+    ) -> (String, Option<(String, String)>) {
+        let truncated = truncate::truncate(&output, limits);
+        if truncated.is_complete() {
+            return (truncated.text, None);
+        }
+        let summary = truncated.summary();
+        let artifact_id = self.artifacts.save(caller.owner.clone(), command, output);
+        (truncated.text, Some((summary, artifact_id)))
+    }
+
+    /// Start an audit record for a tool call made by `peer`
+    fn audit_record(&self, tool: &'static str, peer: &Peer<RoleServer>, client: Option<&Client>) -> AuditRecord {
+        AuditRecord {
+            timestamp: Utc::now(),
+            tool,
+            client: client.map(|c| c.name.clone()).or_else(|| client_identity(peer)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Who made a tool call
+struct Caller {
+    /// Authenticated client, when the call came over HTTP with
+    /// authentication. Callers without one are not limited by scopes.
+    client: Option<Arc<Client>>,
+    /// Identity that owns the sessions the caller creates: the client name,
+    /// or the connection for unauthenticated HTTP. `None` for stdio, which
+    /// may use every session.
+    owner: Option<String>,
+}
+
+/// Identify the caller from the HTTP request the tool call arrived in
+fn identify(extensions: &Extensions) -> Caller {
+    let Some(parts) = extensions.get::<Parts>() else {
+        return Caller {
+            client: None,
+            owner: None,
+        };
+    };
+    let client = parts.extensions.get::<Arc<Client>>().cloned();
+    let connection = parts
+        .extensions
+        .get::<ConnectionId>()
+        .map(|id| id.0.clone())
+        .or_else(|| {
+            parts
+                .headers
+                .get("mcp-session-id")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        });
+    let owner = match &client {
+        Some(client) => Some(client.name.clone()),
+        None => connection.map(|id| format!("connection:{}", id)),
+    };
+    Caller { client, owner }
+}
+
+/// Identify the client by the name and version it sent in `initialize`
+fn client_identity(peer: &Peer<RoleServer>) -> Option<String> {
+    peer.peer_info()
+        .map(|info| format!("{}/{}", info.client_info.name, info.client_info.version))
+}
+
+/// Extra header line marking redacted secrets in a tool result, if any
+fn redaction_note(redactions: &redact::Redactions) -> String {
+    if redactions.is_empty() {
+        String::new()
+    } else {
+        format!("\nRedacted: {}", redact::summarize(redactions))
+    }
 }
 
 // -- Tool parameter types --
 
+/// Response requested from the user before running a risky command
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct Confirmation {
+    /// Run the command
+    pub approve: bool,
+}
+
+rmcp::elicit_safe!(Confirmation);
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ExecuteParams {
     /// Shell command to execute (e.g. "ls -la", "cargo build")
@@ -34,6 +460,46 @@ pub struct ExecuteParams {
     pub cwd: Option<String>,
     /// Timeout in seconds. Default: 300 (5 min)
     pub timeout_secs: Option<u64>,
+    /// Most output lines to return, keeping the first quarter and the rest from the end; 0 for no limit. Default: server setting (500)
+    pub max_lines: Option<usize>,
+    /// Most output bytes to return, cut the same way; 0 for no limit. Default: server setting (50 KB)
+    pub max_bytes: Option<usize>,
+    /// When the output is over the limits, collapse runs of lines that repeat or differ only in numbers, such as progress output. Default: true
+    pub dedup: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExecuteBatchParams {
+    /// Shell commands to run (e.g. ["cargo fmt --check", "cargo clippy", "cargo test"])
+    pub commands: Vec<String>,
+    /// Working directory of every command. Defaults to server's cwd
+    pub cwd: Option<String>,
+    /// Timeout of each command in seconds. Default: 300 (5 min)
+    pub timeout_secs: Option<u64>,
+    /// "sequential" (default) runs the commands in order; "parallel" runs up to max_parallel at once
+    pub mode: Option<String>,
+    /// Most commands to run at once in parallel mode. Default: 4
+    pub max_parallel: Option<usize>,
+    /// Skip the commands not yet started once one fails. Default: true
+    pub stop_on_failure: Option<bool>,
+    /// Most output lines to return per command; 0 for no limit. Default: server setting (500)
+    pub max_lines: Option<usize>,
+    /// Most output bytes to return per command; 0 for no limit. Default: server setting (50 KB)
+    pub max_bytes: Option<usize>,
+    /// When the output is over the limits, collapse runs of lines that repeat or differ only in numbers, such as progress output. Default: true
+    pub dedup: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ReadArtifactParams {
+    /// Artifact ID from a truncated execute or execute_batch result
+    pub artifact_id: String,
+    /// First line to return (1-based). Default: 1
+    pub start_line: Option<usize>,
+    /// Most lines to return. Default: 200
+    pub lines: Option<usize>,
+    /// Regex; only return matching lines (e.g. "error|FAILED")
+    pub pattern: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -44,11 +510,39 @@ pub struct CreateSessionParams {
     pub shell: Option<String>,
     /// Project name for tagging/filtering
     pub project: Option<String>,
+    /// Unique name (e.g. "devserver", "db") usable in place of the session_id in every tool
+    pub name: Option<String>,
+    /// Key/value labels to find the session by with list_sessions (e.g. {"role": "server", "env": "dev"})
+    pub labels: Option<BTreeMap<String, String>>,
+    /// What the session is for
+    pub description: Option<String>,
+    /// Regex patterns (e.g. "panicked at", "error\\[E\\d+\\]"); a notification is sent when a line of output matches. See watch_session for other actions
+    pub watch: Option<Vec<String>>,
+    /// Record output and input to an asciicast file (see export_recording). Defaults to the server's MCP_TERMINAL_RECORD setting
+    pub record: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ForkSessionParams {
+    /// Session to fork, by ID or name
+    pub session_id: String,
+    /// Unique name for the new session, usable in place of its session_id
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct UpdateSessionParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Labels to set; a null value removes the label. Other labels are kept
+    pub labels: Option<HashMap<String, Option<String>>>,
+    /// New description; an empty string removes it
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct SendInputParams {
-    /// Session ID returned by create_session
+    /// Session ID returned by create_session, or the session's name
     pub session_id: String,
     /// Text to send to the terminal (include \\n for Enter)
     pub input: String,
@@ -56,7 +550,7 @@ pub struct SendInputParams {
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ReadOutputParams {
-    /// Session ID returned by create_session
+    /// Session ID returned by create_session, or the session's name
     pub session_id: String,
     /// Max number of lines to return (from the end). Omit for all
     pub lines: Option<u32>,
@@ -64,39 +558,459 @@ pub struct ReadOutputParams {
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct CloseSessionParams {
-    /// Session ID returned by create_session
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ShareSessionParams {
+    /// Session ID returned by create_session, or the session's name
     pub session_id: String,
+    /// Client to share the session with, or "*" for every client
+    pub client: String,
+    /// Revoke a previous grant instead. Default: false
+    pub revoke: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct WatchSessionParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Regex matched against each line of output (e.g. "error\\[E\\d+\\]", "panicked at", "Listening on")
+    pub pattern: String,
+    /// What to do on a match: "record", "notify", "send_input" or "signal". Every match is recorded. Default: "notify"
+    pub action: Option<String>,
+    /// Input to send for the send_input action (include \\n for Enter)
+    pub input: Option<String>,
+    /// Signal for the signal action (e.g. "SIGINT", "SIGTERM", "SIGHUP")
+    pub signal: Option<String>,
+    /// Remove the trigger after its first match. Default: false
+    pub once: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct UnwatchSessionParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Trigger ID returned by watch_session
+    pub trigger_id: u32,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListWatchHitsParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Only return hits with a greater hit_id, to poll for new ones
+    pub after_hit_id: Option<u64>,
+    /// Only return hits of this trigger
+    pub trigger_id: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetRecordingParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Start (true) or pause (false) recording
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportRecordingParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// File to write the recording to. Omit to return it inline (up to 1 MB)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportTranscriptParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// "markdown" (default) or "html" (self-contained page with the terminal's colors)
+    pub format: Option<String>,
+    /// File to write the transcript to. Omit to return it inline (up to 1 MB)
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SessionHistoryParams {
+    /// Session ID returned by create_session, or the session's name
+    pub session_id: String,
+    /// Fetch this command with its output instead of listing commands
+    pub command_id: Option<u64>,
+    /// When listing, only return commands with a greater command_id
+    pub after_command_id: Option<u64>,
+    /// When listing, return at most this many of the newest commands. Default: 50
+    pub limit: Option<usize>,
+    /// When fetching a command, max number of output lines to return (from the end). Omit for all
+    pub lines: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ListSessionsParams {
     /// Filter by project name
     pub project: Option<String>,
+    /// Label selector: comma-separated key=value, key!=value, key (has the label) or !key (lacks it), all of which must match
+    pub labels: Option<String>,
+    /// Only live (true) or only exited and reaped (false) sessions
+    pub alive: Option<bool>,
+    /// Shell program, as a path or name (e.g. "zsh")
+    pub shell: Option<String>,
+    /// Only sessions created at least this many seconds ago
+    pub min_age_secs: Option<u64>,
+    /// Only sessions created at most this many seconds ago
+    pub max_age_secs: Option<u64>,
+    /// Only sessions whose foreground process's command line contains this text (e.g. "cargo", "npm run dev")
+    pub foreground: Option<String>,
+    /// "created" (default), "idle", "name" or "project"
+    pub sort_by: Option<String>,
+    /// Sort in descending order. Default: false
+    pub descending: Option<bool>,
+}
+
+// -- Prompt argument types --
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DiagnoseFailureArgs {
+    /// Session in which the command failed, by ID or name
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RunTestsArgs {
+    /// Session to run the tests in, by ID or name. Defaults to a one-off `execute`
+    pub session_id: Option<String>,
+    /// Test command (e.g. "cargo test", "npm test"). Detected from the project when omitted
+    pub command: Option<String>,
+    /// Project directory. Defaults to the session's cwd
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetupDevEnvironmentArgs {
+    /// Project directory
+    pub cwd: String,
+    /// Project name to tag the sessions with
+    pub project: Option<String>,
+}
+
+#[prompt_router]
+impl TerminalServer {
+    /// Find out why the last command in a session failed and how to fix it
+    #[prompt(name = "diagnose_failure")]
+    async fn diagnose_failure(
+        &self,
+        Parameters(args): Parameters<DiagnoseFailureArgs>,
+        extensions: Extensions,
+    ) -> Result<Vec<PromptMessage>, McpError> {
+        let context = self.session_context(&identify(&extensions), &args.session_id)?;
+        Ok(vec![PromptMessage::new_text(
+            PromptMessageRole::User,
+            format!(
+                "{}\n\nThe last command in this session failed. Identify the failing command and its \
+                 error, explain the root cause, and propose a fix. Use read_output or execute to gather \
+                 more information if the output above is not enough, but don't change anything until \
+                 I agree to the fix.",
+                context
+            ),
+        )])
+    }
+
+    /// Run the project's test suite and summarize the failures
+    #[prompt(name = "run_tests")]
+    async fn run_tests(
+        &self,
+        Parameters(args): Parameters<RunTestsArgs>,
+        extensions: Extensions,
+    ) -> Result<Vec<PromptMessage>, McpError> {
+        let caller = identify(&extensions);
+        let command = match &args.command {
+            Some(command) => format!("`{}`", command),
+            None => "the project's test command (detect it from the build files)".to_string(),
+        };
+        let mut text = match &args.session_id {
+            Some(session_id) => format!(
+                "{}\n\nRun {} in session {} with send_input, then poll read_output until it finishes.",
+                self.session_context(&caller, session_id)?,
+                command,
+                session_id
+            ),
+            None => format!("Run {} with execute.", command),
+        };
+        if let Some(cwd) = &args.cwd {
+            text.push_str(&format!(" Run it in {}.", cwd));
+        }
+        text.push_str(
+            "\n\nThen summarize the results: how many tests passed and failed, and for each failure \
+             the test name, the assertion or error message, and the likely cause. Group failures that \
+             share a cause. Don't fix anything yet.",
+        );
+        Ok(vec![PromptMessage::new_text(PromptMessageRole::User, text)])
+    }
+
+    /// Install a project's dependencies and start its development services
+    #[prompt(name = "setup_dev_environment")]
+    async fn setup_dev_environment(
+        &self,
+        Parameters(args): Parameters<SetupDevEnvironmentArgs>,
+        extensions: Extensions,
+    ) -> Result<Vec<PromptMessage>, McpError> {
+        let caller = identify(&extensions);
+        let project = args.project.as_deref();
+        let mut text = format!(
+            "Set up the development environment of the project in {}. Read its README and build files \
+             to find out how. Install dependencies and build with execute. Start long-running services \
+             (dev servers, watchers, databases) each in its own session with get_or_create_session, \
+             named after the service (e.g. \"devserver\", \"db\"){}, and check with read_output that \
+             they came up. Finish with a short summary of what runs where.",
+            args.cwd,
+            project.map(|p| format!(" tagged with project \"{}\"", p)).unwrap_or_default()
+        );
+
+        // Avoid starting services twice
+        let mut sessions = self.session_manager.list_sessions(project, caller.owner.as_deref());
+        sessions.retain(|s| s.is_alive && caller.client.as_ref().is_none_or(|c| c.allows_project(s.project.as_deref())));
+        if !sessions.is_empty() {
+            let running = sessions
+                .iter()
+                .map(|s| {
+                    let session = match &s.name {
+                        Some(name) => format!("{} ({})", name, s.session_id),
+                        None => s.session_id.clone(),
+                    };
+                    match &s.foreground {
+                        Some(foreground) => format!(
+                            "- {} (cwd: {}, running: {})",
+                            session,
+                            s.current_cwd.as_ref().unwrap_or(&s.cwd),
+                            foreground.command
+                        ),
+                        None => format!("- {} (cwd: {})", session, s.current_cwd.as_ref().unwrap_or(&s.cwd)),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            text.push_str(&format!(
+                "\n\nThese sessions are already running; check them with read_output before starting \
+                 anything they may already provide:\n{}",
+                running
+            ));
+        }
+        Ok(vec![PromptMessage::new_text(PromptMessageRole::User, text)])
+    }
 }
 
 #[tool_router]
 impl TerminalServer {
-    #[tool(description = "Execute a shell command synchronously. Waits for completion and returns stdout and exit code. Use for simple one-off commands.")]
-    async fn execute(&self, Parameters(params): Parameters<ExecuteParams>) -> String {
+    #[tool(description = "Execute a shell command synchronously. Waits for completion and returns stdout, exit code and resource usage (wall/CPU time, peak RSS, I/O). Use for simple one-off commands.")]
+    async fn execute(&self, Parameters(params): Parameters<ExecuteParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
         tracing::info!(command = %params.command, cwd = ?params.cwd, "Executing command");
 
-        match self.session_manager.execute(&params.command, params.cwd, params.timeout_secs) {
+        let caller = identify(&extensions);
+        let started = Instant::now();
+        let mut record = self.audit_record("execute", &peer, caller.client.as_deref());
+        record.command = Some(params.command.clone());
+        record.cwd = params.cwd.clone().or_else(|| {
+            std::env::current_dir().ok().map(|p| p.to_string_lossy().to_string())
+        });
+
+        let authorized = caller.client.as_ref().map_or(Ok(()), |c| c.check_scope(Scope::Execute));
+        let confirmed = match authorized {
+            Ok(()) => {
+                self.confirm_commands(&peer, std::slice::from_ref(&params.command), record.cwd.as_deref().unwrap_or_default())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let result = match confirmed {
+            // The command blocks for up to its timeout, so keep it off the runtime
+            Ok(()) => {
+                let session_manager = Arc::clone(&self.session_manager);
+                let command = params.command.clone();
+                tokio::task::spawn_blocking(move || session_manager.execute(&command, params.cwd, params.timeout_secs))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Command failed: {}", e)))
+            }
+            Err(e) => Err(e),
+        };
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
             Ok(result) => {
+                record.exit_code = Some(result.exit_code);
+                record.output_bytes = Some(result.stdout.len());
+            }
+            Err(e) => record.error = Some(e.clone()),
+        }
+        self.audit.record(record);
+
+        match result {
+            Ok(result) => {
+                let limits = self.output_limits.with(params.max_lines, params.max_bytes, params.dedup);
+                let (output, truncated) = self.fit_output(&caller, &params.command, result.stdout, &limits);
+                let truncation_note = match truncated {
+                    Some((summary, artifact_id)) => format!(
+                        "\nTruncated: {}; full output: read_artifact {}",
+                        summary, artifact_id
+                    ),
+                    None => String::new(),
+                };
                 format!(
-                    "Exit code: {}\n\n{}",
+                    "Exit code: {}\nResources: {}{}{}\n\n{}",
                     result.exit_code,
-                    result.stdout
+                    result.usage,
+                    redaction_note(&result.redactions),
+                    truncation_note,
+                    output
                 )
             }
             Err(e) => format!("ERROR: {}", e),
         }
     }
 
-    #[tool(description = "Create a new interactive terminal session with a PTY. Returns a session_id for subsequent send_input/read_output calls. Use for long-running or interactive commands.")]
-    async fn create_session(&self, Parameters(params): Parameters<CreateSessionParams>) -> String {
-        tracing::info!(cwd = ?params.cwd, project = ?params.project, "Creating session");
+    #[tool(description = "Execute several shell commands in one call, sequentially (stopping at the first failure by default) or in parallel with a concurrency limit, e.g. fmt, clippy and test. Returns a summary and each command's status, exit code, duration, resource usage and output.")]
+    async fn execute_batch(&self, Parameters(params): Parameters<ExecuteBatchParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
+        tracing::info!(commands = params.commands.len(), mode = ?params.mode, cwd = ?params.cwd, "Executing batch");
+
+        let caller = identify(&extensions);
+        let started = Instant::now();
+        let cwd = params.cwd.clone().or_else(|| {
+            std::env::current_dir().ok().map(|p| p.to_string_lossy().to_string())
+        });
+
+        let authorized = caller.client.as_ref().map_or(Ok(()), |c| c.check_scope(Scope::Execute));
+        let checked = authorized
+            .and_then(|()| match params.commands.len() {
+                0 => Err("No commands to run".to_string()),
+                n if n > batch::MAX_COMMANDS => Err(format!("Too many commands ({}); the limit is {}", n, batch::MAX_COMMANDS)),
+                _ => Ok(()),
+            })
+            .and_then(|()| batch::Mode::parse(params.mode.as_deref(), params.max_parallel));
+        // One confirmation covers every risky command in the batch
+        let confirmed = match checked {
+            Ok(mode) => self
+                .confirm_commands(&peer, &params.commands, cwd.as_deref().unwrap_or_default())
+                .await
+                .map(|()| mode),
+            Err(e) => Err(e),
+        };
+        let mode = match confirmed {
+            Ok(mode) => mode,
+            Err(e) => {
+                let mut record = self.audit_record("execute_batch", &peer, caller.client.as_deref());
+                record.command = Some(params.commands.join("\n"));
+                record.cwd = cwd;
+                record.duration_ms = started.elapsed().as_millis() as u64;
+                record.error = Some(e.clone());
+                self.audit.record(record);
+                return format!("ERROR: {}", e);
+            }
+        };
+
+        // Commands block for up to their timeout, so keep them off the runtime
+        let session_manager = Arc::clone(&self.session_manager);
+        let commands = params.commands.clone();
+        let batch_cwd = params.cwd.clone();
+        let stop_on_failure = params.stop_on_failure.unwrap_or(true);
+        let ran = tokio::task::spawn_blocking(move || {
+            batch::run(
+                &session_manager,
+                &commands,
+                batch_cwd.as_deref(),
+                params.timeout_secs,
+                mode,
+                stop_on_failure,
+            )
+        })
+        .await;
+        let mut results = match ran {
+            Ok(results) => results,
+            Err(e) => return format!("ERROR: Batch failed: {}", e),
+        };
+
+        // One audit record per command run, as for execute
+        for result in results.iter().filter(|r| r.status != Status::Skipped) {
+            let mut record = self.audit_record("execute_batch", &peer, caller.client.as_deref());
+            record.command = Some(result.command.clone());
+            record.cwd = cwd.clone();
+            record.exit_code = result.exit_code;
+            record.duration_ms = result.duration_ms;
+            record.output_bytes = result.exit_code.map(|_| result.output.len());
+            record.error = result.error.clone();
+            self.audit.record(record);
+        }
+
+        let limits = self.output_limits.with(params.max_lines, params.max_bytes, params.dedup);
+        for result in &mut results {
+            let output = std::mem::take(&mut result.output);
+            let (output, truncated) = self.fit_output(&caller, &result.command, output, &limits);
+            result.output = output;
+            if let Some((summary, artifact_id)) = truncated {
+                result.truncated = Some(summary);
+                result.artifact_id = Some(artifact_id);
+            }
+        }
+
+        let count = |status: Status| results.iter().filter(|r| r.status == status).count();
+        let summary = serde_json::json!({
+            "succeeded": count(Status::Succeeded),
+            "failed": count(Status::Failed),
+            "errors": count(Status::Error),
+            "skipped": count(Status::Skipped),
+            "duration_ms": started.elapsed().as_millis() as u64,
+            "results": results,
+        });
+        serde_json::to_string_pretty(&summary).unwrap_or_else(|e| format!("ERROR: {}", e))
+    }
+
+    #[tool(description = "Read the full output of a truncated execute or execute_batch result by its artifact_id: a range of lines, or only the lines matching a regex. Artifacts are kept in memory until the server restarts or needs room.")]
+    async fn read_artifact(&self, Parameters(params): Parameters<ReadArtifactParams>, extensions: Extensions) -> String {
+        let caller = identify(&extensions);
+        if let Some(Err(e)) = caller.client.as_ref().map(|c| c.check_scope(Scope::Execute)) {
+            return format!("ERROR: {}", e);
+        }
+        let pattern = match params.pattern.as_deref().map(regex::Regex::new).transpose() {
+            Ok(pattern) => pattern,
+            Err(e) => return format!("ERROR: Invalid pattern: {}", e),
+        };
 
-        match self.session_manager.create_session(params.cwd, params.shell, params.project) {
+        let page = self.artifacts.read(
+            &params.artifact_id,
+            caller.owner.as_deref(),
+            params.start_line.unwrap_or(1),
+            params.lines.unwrap_or(DEFAULT_ARTIFACT_LINES),
+            pattern.as_ref(),
+        );
+        match page {
+            Ok(page) => {
+                let shown = match (page.lines.first(), page.lines.last()) {
+                    (Some((first, _)), Some((last, _))) => format!("lines {}-{}", first, last),
+                    _ => "no lines".to_string(),
+                };
+                let mut text = format!(
+                    "Artifact: {}\nCommand: {} (run at {})\nShowing {} of {}{}\n\n",
+                    page.artifact_id,
+                    page.command,
+                    page.created_at.to_rfc3339(),
+                    shown,
+                    page.total_lines,
+                    if page.more { " (more after these)" } else { "" }
+                );
+                for (number, line) in &page.lines {
+                    text.push_str(&format!("{}: {}\n", number, line));
+                }
+                text
+            }
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Create a new interactive terminal session with a PTY. Returns a session_id for subsequent send_input/read_output calls. Use for long-running or interactive commands. Give it a name to refer to it by name instead of session_id.")]
+    async fn create_session(&self, Parameters(params): Parameters<CreateSessionParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
+        tracing::info!(cwd = ?params.cwd, project = ?params.project, name = ?params.name, "Creating session");
+
+        let caller = identify(&extensions);
+        match self.create("create_session", params, &caller, &peer) {
             Ok(session_id) => {
                 serde_json::json!({ "session_id": session_id }).to_string()
             }
@@ -104,26 +1018,138 @@ impl TerminalServer {
         }
     }
 
+    #[tool(description = "Return your live session with the given name, or create it if there is none, e.g. to reuse a \"devserver\" session started earlier instead of starting a second one. The other parameters only apply when the session is created. Returns the session_id and whether it was created.")]
+    async fn get_or_create_session(&self, Parameters(params): Parameters<CreateSessionParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
+        tracing::info!(cwd = ?params.cwd, project = ?params.project, name = ?params.name, "Getting or creating session");
+
+        let caller = identify(&extensions);
+        let Some(name) = &params.name else {
+            return "ERROR: get_or_create_session requires a name".to_string();
+        };
+        let name = name.clone();
+        let existing = |session_id: String| {
+            self.authorize_session(&caller, Scope::Sessions, &session_id)
+                .map(|()| (session_id, false))
+        };
+        let result = match self.session_manager.named_session(&name, caller.owner.as_deref()) {
+            Some(session_id) => existing(session_id),
+            None => match self.create("get_or_create_session", params, &caller, &peer) {
+                Ok(session_id) => Ok((session_id, true)),
+                // A concurrent call may have created it first
+                Err(e) => match self.session_manager.named_session(&name, caller.owner.as_deref()) {
+                    Some(session_id) => existing(session_id),
+                    None => Err(e),
+                },
+            },
+        };
+        match result {
+            Ok((session_id, created)) => {
+                serde_json::json!({ "session_id": session_id, "created": created }).to_string()
+            }
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Create a new session in the same place as an existing one: same shell, current working directory, exported environment (bash/zsh with shell integration) and project. Use for a second terminal, e.g. to run tests while a dev server occupies the first. Returns the new session_id.")]
+    async fn fork_session(&self, Parameters(params): Parameters<ForkSessionParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, "Forking session");
+
+        let caller = identify(&extensions);
+        let forked_from = self.resolve_own(&caller, &params.session_id);
+        let started = Instant::now();
+        let mut record = self.audit_record("fork_session", &peer, caller.client.as_deref());
+
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &forked_from)
+            .and_then(|()| {
+                self.session_manager
+                    .fork_session(&forked_from, caller.owner.clone(), params.name)
+            });
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok((session_id, _)) => {
+                record.cwd = self.session_manager.session_cwd(session_id);
+                record.session_id = Some(session_id.clone());
+            }
+            Err(e) => record.error = Some(e.clone()),
+        }
+        self.audit.record(record);
+
+        match result {
+            Ok((session_id, env_copied)) => serde_json::json!({
+                "session_id": session_id,
+                "forked_from": forked_from,
+                "env_copied": env_copied,
+            })
+            .to_string(),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Set or remove a session's labels and replace its description. Returns the updated session info.")]
+    async fn update_session(&self, Parameters(params): Parameters<UpdateSessionParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, "Updating session");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| {
+                self.session_manager.update_session(
+                    &session_id,
+                    params.labels.unwrap_or_default(),
+                    params.description,
+                )
+            });
+        match result {
+            Ok(info) => serde_json::to_string_pretty(&info).unwrap_or_else(|e| format!("ERROR: {}", e)),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
     #[tool(description = "Send input text to an interactive terminal session. Include newline character to submit commands.")]
-    async fn send_input(&self, Parameters(params): Parameters<SendInputParams>) -> String {
+    async fn send_input(&self, Parameters(params): Parameters<SendInputParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
         tracing::info!(session_id = %params.session_id, "Sending input");
 
-        match self.session_manager.send_input(&params.session_id, &params.input) {
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let started = Instant::now();
+        let mut record = self.audit_record("send_input", &peer, caller.client.as_deref());
+        record.session_id = Some(session_id.clone());
+        record.cwd = self.session_manager.session_cwd(&session_id);
+        record.input = Some(params.input.clone());
+
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| self.session_manager.send_input(&session_id, &params.input));
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        record.error = result.as_ref().err().cloned();
+        self.audit.record(record);
+
+        match result {
             Ok(()) => "Input sent".to_string(),
             Err(e) => format!("ERROR: {}", e),
         }
     }
 
     #[tool(description = "Read accumulated output from a terminal session. This is a destructive read - the buffer is cleared after reading. Returns the output text and whether the session is still alive.")]
-    async fn read_output(&self, Parameters(params): Parameters<ReadOutputParams>) -> String {
+    async fn read_output(&self, Parameters(params): Parameters<ReadOutputParams>, extensions: Extensions) -> String {
         tracing::info!(session_id = %params.session_id, "Reading output");
 
-        match self.session_manager.read_output(&params.session_id, params.lines) {
-            Ok((output, is_alive)) => {
+        let caller = identify(&extensions);
+        let session_id = self.resolve(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Observe, &session_id)
+            .and_then(|()| self.session_manager.read_output(&session_id, params.lines));
+        match result {
+            Ok(result) => {
                 format!(
-                    "alive: {}\n\n{}",
-                    is_alive,
-                    if output.is_empty() { "(no new output)" } else { &output }
+                    "alive: {}{}\n\n{}",
+                    result.is_alive,
+                    redaction_note(&result.redactions),
+                    if result.output.is_empty() { "(no new output)" } else { &result.output }
                 )
             }
             Err(e) => format!("ERROR: {}", e),
@@ -131,18 +1157,254 @@ impl TerminalServer {
     }
 
     #[tool(description = "Close and terminate a terminal session. The PTY and child process are killed.")]
-    async fn close_session(&self, Parameters(params): Parameters<CloseSessionParams>) -> String {
+    async fn close_session(&self, Parameters(params): Parameters<CloseSessionParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
         tracing::info!(session_id = %params.session_id, "Closing session");
 
-        match self.session_manager.close_session(&params.session_id) {
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let started = Instant::now();
+        let mut record = self.audit_record("close_session", &peer, caller.client.as_deref());
+        record.session_id = Some(session_id.clone());
+        record.cwd = self.session_manager.session_cwd(&session_id);
+
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| self.session_manager.close_session(&session_id));
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        record.error = result.as_ref().err().cloned();
+        self.audit.record(record);
+
+        match result {
             Ok(()) => "Session closed".to_string(),
             Err(e) => format!("ERROR: {}", e),
         }
     }
 
-    #[tool(description = "List all active terminal sessions. Optionally filter by project name.")]
-    async fn list_sessions(&self, Parameters(params): Parameters<ListSessionsParams>) -> String {
-        let sessions = self.session_manager.list_sessions(params.project.as_deref());
+    #[tool(description = "Share a terminal session you own with another client (or \"*\" for every client) so it can send input, read output and close it. Set revoke to take access back.")]
+    async fn share_session(&self, Parameters(params): Parameters<ShareSessionParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, client = %params.client, revoke = ?params.revoke, "Sharing session");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| {
+                self.session_manager.share_session(
+                    &session_id,
+                    caller.owner.as_deref(),
+                    &params.client,
+                    params.revoke.unwrap_or(false),
+                )
+            });
+
+        match result {
+            Ok(shared_with) => serde_json::json!({ "shared_with": shared_with }).to_string(),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Register a regex trigger on a session's output. On each matching line the hit is recorded and the action runs: record (nothing else), notify (send a notifications/terminal/session notification), send_input, or signal. Returns the trigger_id.")]
+    async fn watch_session(&self, Parameters(params): Parameters<WatchSessionParams>, peer: Peer<RoleServer>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, pattern = %params.pattern, action = ?params.action, "Watching session");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let started = Instant::now();
+        let mut record = self.audit_record("watch_session", &peer, caller.client.as_deref());
+        record.session_id = Some(session_id.clone());
+        record.cwd = self.session_manager.session_cwd(&session_id);
+        // The trigger as "<pattern> -> <action>", e.g. "Listening on -> signal SIGHUP"
+        let action = params.action.as_deref().unwrap_or("notify");
+        record.command = Some(match &params.signal {
+            Some(signal) if action == "signal" => format!("{} -> {} {}", params.pattern, action, signal),
+            _ => format!("{} -> {}", params.pattern, action),
+        });
+        record.input = params.input.clone();
+
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| {
+                WatchAction::parse(action, params.input, params.signal)
+            })
+            .and_then(|action| {
+                self.session_manager.watch_session(
+                    &session_id,
+                    &params.pattern,
+                    action,
+                    params.once.unwrap_or(false),
+                )
+            });
+
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        record.error = result.as_ref().err().cloned();
+        self.audit.record(record);
+
+        match result {
+            Ok(trigger_id) => serde_json::json!({ "trigger_id": trigger_id }).to_string(),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Remove a trigger registered with watch_session.")]
+    async fn unwatch_session(&self, Parameters(params): Parameters<UnwatchSessionParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, trigger_id = params.trigger_id, "Unwatching session");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| self.session_manager.unwatch_session(&session_id, params.trigger_id));
+        match result {
+            Ok(()) => "Trigger removed".to_string(),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "List a session's watch triggers and their recent hits (matching output lines). Non-destructive; pass after_hit_id to get only new hits.")]
+    async fn list_watch_hits(&self, Parameters(params): Parameters<ListWatchHitsParams>, extensions: Extensions) -> String {
+        let caller = identify(&extensions);
+        let session_id = self.resolve(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Observe, &session_id)
+            .and_then(|()| {
+                self.session_manager
+                    .watch_hits(&session_id, params.after_hit_id, params.trigger_id)
+            });
+        match result {
+            Ok((triggers, hits)) => serde_json::to_string_pretty(&serde_json::json!({
+                "triggers": triggers,
+                "hits": hits,
+            }))
+            .unwrap_or_else(|e| format!("ERROR: {}", e)),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Start or pause recording a session's output and input (with timestamps) to an asciicast v2 file. Pausing keeps what was recorded so far.")]
+    async fn set_recording(&self, Parameters(params): Parameters<SetRecordingParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, enabled = params.enabled, "Setting recording");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve_own(&caller, &params.session_id);
+        let result = self
+            .authorize_session(&caller, Scope::Sessions, &session_id)
+            .and_then(|()| self.session_manager.set_recording(&session_id, params.enabled));
+        match result {
+            Ok(()) if params.enabled => "Recording".to_string(),
+            Ok(()) => "Recording paused".to_string(),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Export a session's recording as asciicast v2 (playable with `asciinema play`), with secrets redacted. Works after the session has exited, been reaped or been closed (pass its session_id). Returned inline, or written to path.")]
+    async fn export_recording(&self, Parameters(params): Parameters<ExportRecordingParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, path = ?params.path, "Exporting recording");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve(&caller, &params.session_id);
+        // Writing a file is more than observing
+        let scope = if params.path.is_some() { Scope::Sessions } else { Scope::Observe };
+        let result = self
+            .session_manager
+            .recording_project(&session_id, caller.owner.as_deref())
+            .and_then(|project| match &caller.client {
+                Some(client) => client.authorize(scope, project.as_deref()),
+                None => Ok(()),
+            })
+            .and_then(|()| {
+                self.session_manager
+                    .export_recording(&session_id, params.path.as_deref())
+            });
+        match (result, &params.path) {
+            (Ok(cast), Some(path)) => serde_json::json!({ "path": path, "bytes": cast.len() }).to_string(),
+            (Ok(cast), None) => cast,
+            (Err(e), _) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "Export a session's output history (not drained by read_output) as a Markdown or HTML transcript for sharing, e.g. in code reviews. With shell integration it is split into commands with their prompts, outputs and exit codes. Secrets are redacted. Returned inline, or written to path.")]
+    async fn export_transcript(&self, Parameters(params): Parameters<ExportTranscriptParams>, extensions: Extensions) -> String {
+        tracing::info!(session_id = %params.session_id, format = ?params.format, path = ?params.path, "Exporting transcript");
+
+        let caller = identify(&extensions);
+        let session_id = self.resolve(&caller, &params.session_id);
+        // Writing a file is more than observing
+        let scope = if params.path.is_some() { Scope::Sessions } else { Scope::Observe };
+        let result = self
+            .authorize_session(&caller, scope, &session_id)
+            .and_then(|()| transcript::Format::parse(params.format.as_deref()))
+            .and_then(|format| {
+                self.session_manager
+                    .export_transcript(&session_id, format, params.path.as_deref())
+            });
+        match (result, &params.path) {
+            (Ok(transcript), Some(path)) => serde_json::json!({ "path": path, "bytes": transcript.len() }).to_string(),
+            (Ok(transcript), None) => transcript,
+            (Err(e), _) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "List the commands run in a session with their start/end times and exit codes (exit codes need shell integration), or pass command_id to fetch one command with its output. Non-destructive; output stays available while it fits in the session's scrollback.")]
+    async fn session_history(&self, Parameters(params): Parameters<SessionHistoryParams>, extensions: Extensions) -> String {
+        let caller = identify(&extensions);
+        let session_id = self.resolve(&caller, &params.session_id);
+        if let Err(e) = self.authorize_session(&caller, Scope::Observe, &session_id) {
+            return format!("ERROR: {}", e);
+        }
+
+        let result = match params.command_id {
+            Some(command_id) => self
+                .session_manager
+                .history_command(&session_id, command_id, params.lines)
+                .and_then(|(command, output, truncated)| {
+                    let mut value = serde_json::to_value(command).map_err(|e| e.to_string())?;
+                    value["output"] = output.into();
+                    value["output_truncated"] = truncated.into();
+                    Ok(value)
+                }),
+            None => self
+                .session_manager
+                .history(&session_id, params.after_command_id, params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+                .map(|commands| serde_json::json!({ "commands": commands })),
+        };
+        match result {
+            Ok(value) => serde_json::to_string_pretty(&value).unwrap_or_else(|e| format!("ERROR: {}", e)),
+            Err(e) => format!("ERROR: {}", e),
+        }
+    }
+
+    #[tool(description = "List the active terminal sessions you own or that were shared with you, with their names, labels, descriptions, current directory and foreground process, plus recently reaped ones with their reap_reason. Filter by project, label selector, alive state, shell, age or foreground command, and sort by creation time, idleness, name or project.")]
+    async fn list_sessions(&self, Parameters(params): Parameters<ListSessionsParams>, extensions: Extensions) -> String {
+        let caller = identify(&extensions);
+        if let Some(Err(e)) = caller.client.as_ref().map(|c| c.check_scope(Scope::Observe)) {
+            return format!("ERROR: {}", e);
+        }
+        let labels = match params.labels.as_deref().map(Selector::parse).transpose() {
+            Ok(labels) => labels,
+            Err(e) => return format!("ERROR: {}", e),
+        };
+        let sort_key = match SortKey::parse(params.sort_by.as_deref()) {
+            Ok(key) => key,
+            Err(e) => return format!("ERROR: {}", e),
+        };
+        let session_filter = SessionFilter {
+            labels,
+            alive: params.alive,
+            shell: params.shell,
+            min_age: params.min_age_secs.map(Duration::from_secs),
+            max_age: params.max_age_secs.map(Duration::from_secs),
+            foreground: params.foreground,
+        };
+
+        let mut sessions = self
+            .session_manager
+            .list_sessions(params.project.as_deref(), caller.owner.as_deref());
+        sessions.retain(|s| session_filter.matches(s));
+        if let Some(client) = &caller.client {
+            sessions.retain(|s| client.allows_project(s.project.as_deref()));
+        }
+        filter::sort(&mut sessions, sort_key, params.descending.unwrap_or(false));
 
         if sessions.is_empty() {
             "No active sessions".to_string()
@@ -153,16 +1415,190 @@ impl TerminalServer {
 }
 
 #[tool_handler]
+#[prompt_handler]
 impl ServerHandler for TerminalServer {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             instructions: Some(
                 "Terminal session manager. Use 'execute' for simple one-off commands, \
+                 'execute_batch' for several at once, \
                  or create_session/send_input/read_output/close_session for interactive terminals."
                     .into(),
             ),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_logging()
+                .enable_prompts()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .build(),
             ..Default::default()
         }
     }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let caller = identify(&context.extensions);
+        if let Some(client) = &caller.client {
+            client
+                .check_scope(Scope::Observe)
+                .map_err(|e| McpError::invalid_request(e, None))?;
+        }
+
+        let resources = self
+            .session_manager
+            .list_sessions(None, caller.owner.as_deref())
+            .iter()
+            .filter(|s| caller.client.as_ref().is_none_or(|c| c.allows_project(s.project.as_deref())))
+            .flat_map(resources::for_session)
+            .collect();
+        Ok(ListResourcesResult::with_all_items(resources))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        Ok(ListResourceTemplatesResult::with_all_items(resources::templates()))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        let caller = identify(&context.extensions);
+        let (session_id, view) = resources::parse(&request.uri).map_err(|e| McpError::resource_not_found(e, None))?;
+        let session_id = &self.resolve(&caller, session_id);
+        self.authorize_resource(&caller, session_id, view)
+            .map_err(|e| McpError::invalid_request(e, None))?;
+        let text = self
+            .read_view(&caller, session_id, view)
+            .map_err(|e| McpError::resource_not_found(e, None))?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: request.uri,
+                mime_type: Some(view.mime_type().to_string()),
+                text,
+                meta: None,
+            }],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let caller = identify(&context.extensions);
+        let (session_id, view) = resources::parse(&request.uri).map_err(|e| McpError::resource_not_found(e, None))?;
+        let session_id = self.resolve(&caller, session_id);
+        self.authorize_resource(&caller, &session_id, view)
+            .map_err(|e| McpError::invalid_request(e, None))?;
+        // Fail early on sessions that do not exist
+        self.session_manager
+            .session_info(&session_id, caller.owner.as_deref())
+            .map_err(|e| McpError::resource_not_found(e, None))?;
+
+        let uri = request.uri;
+        let peer = context.peer;
+        let mut updates = self.session_manager.subscribe_updates();
+        let session_manager = self.session_manager.clone();
+        let connection = self.connection.clone();
+        let notify_uri = uri.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(id) if id != session_id => continue,
+                    // Missed updates may include this session's
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+                tokio::time::sleep(UPDATE_DEBOUNCE).await;
+                // Skip updates that arrived while waiting; this notification covers them
+                updates = updates.resubscribe();
+                let param = ResourceUpdatedNotificationParam { uri: notify_uri.clone() };
+                if peer.notify_resource_updated(param).await.is_err() {
+                    break;
+                }
+                // The last update of a closed or reaped session tells the
+                // client the resource is gone; nothing follows it
+                if !session_manager.is_open(&session_id) {
+                    tracing::debug!(uri = %notify_uri, "Subscribed session is gone");
+                    break;
+                }
+            }
+            // Forget this subscription unless it was replaced by a new one
+            let mut subscriptions = connection.subscriptions.lock().unwrap();
+            if subscriptions.get(&notify_uri).is_some_and(|task| task.id() == tokio::task::id()) {
+                subscriptions.remove(&notify_uri);
+            }
+        });
+
+        tracing::debug!(uri = %uri, "Resource subscribed");
+        if let Some(previous) = self.connection.subscriptions.lock().unwrap().insert(uri, task.abort_handle()) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        if let Some(task) = self.connection.subscriptions.lock().unwrap().remove(&request.uri) {
+            task.abort();
+            tracing::debug!(uri = %request.uri, "Resource unsubscribed");
+        }
+        Ok(())
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let caller = identify(&context.extensions);
+        if let Some(client) = &caller.client {
+            client
+                .check_scope(Scope::Logs)
+                .map_err(|e| McpError::invalid_request(e, None))?;
+        }
+
+        let mut logging = self.connection.logging.lock().unwrap();
+        match logging.as_ref() {
+            Some((level, _)) => {
+                level.send_replace(request.level);
+            }
+            None => {
+                let (level, level_rx) = watch::channel(request.level);
+                let forward = self
+                    .log_messages
+                    .clone()
+                    .forward(context.peer, level_rx, Arc::clone(&self.redactor));
+                *logging = Some((level, tokio::spawn(forward).abort_handle()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        tracing::info!("Client initialized");
+        let caller = identify(&context.extensions);
+        if caller.client.as_ref().is_some_and(|c| !c.has_scope(Scope::Observe)) {
+            return;
+        }
+        let task = tokio::spawn(Self::forward_session_events(
+            Arc::clone(&self.session_manager),
+            Arc::clone(&self.redactor),
+            caller,
+            context.peer,
+        ));
+        *self.connection.session_events.lock().unwrap() = Some(task.abort_handle());
+    }
 }
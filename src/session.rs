@@ -1,39 +1,345 @@
 use chrono::{DateTime, Utc};
-use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
-use std::collections::HashMap;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
 use uuid::Uuid;
 
+use crate::history::{self, History};
+use crate::limits::{ReapReason, SessionLimits};
+use crate::process::{self, ForegroundProcess, ResourceUsage};
+use crate::recording::{self, Recording, RecordingOwner, RecordingPolicy};
+use crate::redact::{Redactions, Redactor};
+use crate::shell_integration::{self, Injection, ShellIntegration, ShellMark, ShellState, ShellTracker};
+use crate::shutdown::ShutdownPolicy;
+use crate::tmux::{self, TmuxMeta, TmuxSession};
+use crate::transcript;
+use crate::watch::{self, TriggerInfo, WatchAction, WatchHit, Watchers};
+
 /// Maximum output buffer size per session (1 MB)
 const MAX_BUFFER_SIZE: usize = 1024 * 1024;
 
-/// A terminal session backed by a PTY
+/// Most output `execute` keeps from the start of a command's output
+const EXEC_HEAD_BYTES: usize = 512 * 1024;
+
+/// Most output `execute` keeps from the end of a command's output
+const EXEC_TAIL_BYTES: usize = 1536 * 1024;
+
+/// Size of session terminals
+const SCREEN_ROWS: u16 = 24;
+const SCREEN_COLS: u16 = 200;
+
+/// Output update events buffered for subscribers before they lag
+const UPDATE_CHANNEL_CAPACITY: usize = 1024;
+
+/// Session events buffered for clients before they lag
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// How long to wait for an exited shell's exit code before reporting the
+/// exit without one
+const EXIT_CODE_WAIT: Duration = Duration::from_secs(1);
+
+/// Longest partial line kept for matching watch patterns
+const MAX_WATCH_LINE: usize = 4096;
+
+/// How often the reaper checks sessions against the configured limits
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long reaped sessions stay visible in `list_sessions`
+const TOMBSTONE_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Maximum number of reaped sessions remembered for `list_sessions`
+const MAX_TOMBSTONES: usize = 100;
+
+/// Longest session name, and longest label name
+const MAX_NAME_LEN: usize = 64;
+
+/// Longest label value
+const MAX_LABEL_VALUE_LEN: usize = 256;
+
+/// Longest session description
+const MAX_DESCRIPTION_LEN: usize = 1024;
+
+/// Environment applied to every shell. Disables pagers to prevent hangs in
+/// a non-fully-functional PTY.
+const SHELL_ENV: &[(&str, &str)] = &[("PAGER", "cat"), ("GIT_PAGER", "cat")];
+
+/// Which process hosts session shells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// PTYs owned by this server process
+    Pty,
+    /// Detached tmux sessions that survive server restarts
+    Tmux,
+}
+
+impl Backend {
+    /// Read the backend from `MCP_TERMINAL_BACKEND` (`pty` or `tmux`).
+    /// Defaults to `pty`.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("MCP_TERMINAL_BACKEND").as_deref() {
+            Err(_) | Ok("") | Ok("pty") => Ok(Self::Pty),
+            Ok("tmux") if tmux::available() => Ok(Self::Tmux),
+            Ok("tmux") => Err("MCP_TERMINAL_BACKEND=tmux, but tmux was not found".to_string()),
+            Ok(other) => Err(format!("Invalid MCP_TERMINAL_BACKEND: {}", other)),
+        }
+    }
+}
+
+/// Where a session's shell runs
+enum Host {
+    /// PTY owned by this process — the shell dies with the server
+    Pty {
+        master: Box<dyn MasterPty + Send>,
+        _child: Box<dyn Child + Send + Sync>,
+    },
+    /// Detached tmux session
+    Tmux(TmuxSession),
+}
+
+/// A terminal session's shell and I/O plumbing
 struct SessionInner {
-    _master: Box<dyn MasterPty + Send>,
+    host: Host,
+    /// PID of the shell
+    pid: Option<u32>,
     writer: Box<dyn Write + Send>,
+    /// Output not yet drained by `read_output`
     output: Arc<Mutex<Vec<u8>>>,
+    /// Recent output, kept regardless of `read_output`
+    scrollback: Arc<Mutex<Vec<u8>>>,
+    /// Terminal emulator rendering the current screen
+    screen: Arc<Mutex<vt100::Parser>>,
     is_alive: Arc<Mutex<bool>>,
+    /// Last input, output or exit
+    last_activity: Arc<Mutex<Instant>>,
+    /// Triggers on the output, matched by the reader thread
+    watchers: Arc<Mutex<Watchers>>,
+    /// asciicast recording of the session's output and input
+    recording: Arc<Mutex<Recording>>,
+    /// Shell integration markers seen in the output
+    shell: Arc<Mutex<ShellTracker>>,
+    /// Commands run in the session
+    history: Arc<Mutex<History>>,
     _reader_handle: std::thread::JoinHandle<()>,
 }
 
+/// Something that happened to a session that clients may want to react to
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SessionEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEventKind {
+    /// The shell exited; the exit code is missing if it could not be collected
+    Exited { exit_code: Option<u32> },
+    /// A line of output matched a trigger with the `notify` action
+    OutputMatched { trigger_id: u32, pattern: String, line: String },
+    /// The reaper removed the session
+    Reaped { reason: ReapReason },
+}
+
+/// Where sessions' reader threads report what they see
+#[derive(Clone)]
+struct Notifiers {
+    /// IDs of sessions with new output or a status change
+    updates: broadcast::Sender<String>,
+    /// Events for clients
+    events: broadcast::Sender<SessionEvent>,
+    /// Work the reader threads leave to the manager
+    tasks: mpsc::UnboundedSender<SessionTask>,
+}
+
+/// Work a reader thread can't do itself
+enum SessionTask {
+    /// Collect the exit code of an exited shell and report the exit
+    Exited { session_id: String },
+    /// Run the input or signal action of a watch trigger
+    WatchAction { session_id: String, action: WatchAction },
+}
+
 /// Public session metadata
 #[derive(Clone, serde::Serialize)]
 pub struct SessionInfo {
     pub session_id: String,
+    /// Name given at creation, usable in place of the session ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub project: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Shell the session runs
+    pub shell_program: String,
+    /// Directory the shell started in
     pub cwd: String,
+    /// Current directory of the foreground process, or as last reported by
+    /// the shell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_cwd: Option<String>,
+    /// Process in the terminal's foreground: the running command, or the
+    /// shell itself while it waits at its prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<ForegroundProcess>,
     pub is_alive: bool,
     pub created_at: DateTime<Utc>,
+    /// Whether the session survives server restarts
+    pub persistent: bool,
+    /// Exit code of the shell, once it has exited
+    pub exit_code: Option<u32>,
+    /// Resource usage of the session's processes
+    pub usage: Option<ResourceUsage>,
+    /// Seconds since the last input, output or exit
+    pub idle_secs: u64,
+    /// Whether output and input are being recorded
+    pub recording: bool,
+    /// Working directory, last exit code and command status reported by
+    /// shell integration, if the shell has it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<ShellState>,
+    /// Client that created the session; anyone may use unowned sessions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Clients the owner shared the session with (`*` for everyone)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared_with: Vec<String>,
+    /// Why the session was removed by the reaper, if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reap_reason: Option<ReapReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reaped_at: Option<DateTime<Utc>>,
 }
 
 /// Full session: inner PTY state + metadata
 struct Session {
     inner: SessionInner,
+    name: Option<String>,
     project: Option<String>,
+    labels: BTreeMap<String, String>,
+    description: Option<String>,
+    owner: Option<String>,
+    shared_with: Vec<String>,
+    shell: String,
     cwd: String,
     created_at: DateTime<Utc>,
+    started: Instant,
+    /// Most recent resource usage sample
+    last_usage: Option<ResourceUsage>,
+    /// Exit code and final resource usage, once the shell has exited
+    exit: Option<(u32, ResourceUsage)>,
+}
+
+impl Session {
+    fn is_alive(&self) -> bool {
+        *self.inner.is_alive.lock().unwrap()
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.inner.last_activity.lock().unwrap().elapsed()
+    }
+
+    /// Whether `caller` may use the session
+    fn accessible_by(&self, caller: Option<&str>) -> bool {
+        can_access(self.owner.as_deref(), &self.shared_with, caller)
+    }
+
+    /// Whether the session has outlived the configured limits
+    fn reap_reason(&self, limits: &SessionLimits) -> Option<ReapReason> {
+        if !self.is_alive() {
+            // Exit counts as the last activity
+            return (self.idle_for() >= limits.exited_retention).then_some(ReapReason::Exited);
+        }
+        if limits.max_lifetime.is_some_and(|max| self.started.elapsed() >= max) {
+            return Some(ReapReason::MaxLifetime);
+        }
+        if limits.idle_timeout.is_some_and(|max| self.idle_for() >= max) {
+            return Some(ReapReason::IdleTimeout);
+        }
+        None
+    }
+
+    /// Process group in the terminal's foreground
+    fn foreground_group(&self) -> Option<u32> {
+        match &self.inner.host {
+            Host::Pty { master, .. } => master.process_group_leader().map(|pgid| pgid as u32),
+            Host::Tmux(_) => process::foreground_group(self.inner.pid?),
+        }
+    }
+
+    /// Current directory of the foreground process, falling back to the
+    /// directory last reported by shell integration
+    fn current_cwd(&self, foreground: Option<u32>) -> Option<String> {
+        if !self.is_alive() {
+            return None;
+        }
+        foreground
+            .or(self.inner.pid)
+            .and_then(process::cwd)
+            .or_else(|| self.inner.shell.lock().unwrap().state().and_then(|s| s.cwd.clone()))
+    }
+
+    /// Public metadata snapshot
+    fn info(&mut self, session_id: &str) -> SessionInfo {
+        let usage = self.poll_usage();
+        let foreground = self.foreground_group().filter(|_| self.is_alive());
+        SessionInfo {
+            session_id: session_id.to_string(),
+            name: self.name.clone(),
+            project: self.project.clone(),
+            labels: self.labels.clone(),
+            description: self.description.clone(),
+            shell_program: self.shell.clone(),
+            cwd: self.cwd.clone(),
+            current_cwd: self.current_cwd(foreground),
+            foreground: foreground.and_then(process::foreground_process),
+            is_alive: self.is_alive(),
+            created_at: self.created_at,
+            persistent: matches!(self.inner.host, Host::Tmux(_)),
+            exit_code: self.exit.as_ref().map(|(code, _)| *code),
+            usage,
+            idle_secs: self.idle_for().as_secs(),
+            recording: self.inner.recording.lock().unwrap().is_enabled(),
+            shell: self.inner.shell.lock().unwrap().state().cloned(),
+            owner: self.owner.clone(),
+            shared_with: self.shared_with.clone(),
+            reap_reason: None,
+            reaped_at: None,
+        }
+    }
+
+    /// Reap the shell if it has exited, and report its current or final
+    /// resource usage
+    fn poll_usage(&mut self) -> Option<ResourceUsage> {
+        if let Some((_, usage)) = &self.exit {
+            return Some(usage.clone());
+        }
+        let pid = self.inner.pid?;
+        if !self.is_alive() {
+            let exit = match &self.inner.host {
+                Host::Pty { .. } => process::try_wait(pid, self.started).ok().flatten(),
+                // Not our child, so there is nothing to reap — tmux keeps the
+                // exit code, and the last sample stands in for final usage
+                Host::Tmux(tmux) => tmux.exit_code().map(|code| {
+                    let mut usage = self.last_usage.clone().unwrap_or_default();
+                    usage.wall_time_ms = self.started.elapsed().as_millis() as u64;
+                    (code, usage)
+                }),
+            };
+            if let Some(exit) = exit {
+                self.exit = Some(exit);
+                return self.exit.as_ref().map(|(_, usage)| usage.clone());
+            }
+        }
+        self.last_usage = process::session_usage(pid, self.started);
+        self.last_usage.clone()
+    }
 }
 
 /// Result of a synchronous command execution
@@ -41,37 +347,344 @@ struct Session {
 pub struct ExecResult {
     pub stdout: String,
     pub exit_code: u32,
+    pub usage: ResourceUsage,
+    /// Secrets redacted from stdout
+    pub redactions: Redactions,
+}
+
+/// Output drained from a session by `read_output`
+pub struct SessionOutput {
+    pub output: String,
+    pub is_alive: bool,
+    /// Secrets redacted from the output
+    pub redactions: Redactions,
 }
 
 /// Manages all terminal sessions
 pub struct SessionManager {
     sessions: Mutex<HashMap<String, Session>>,
+    /// Recently reaped sessions, oldest first
+    reaped: Mutex<VecDeque<SessionInfo>>,
+    /// PIDs of commands currently running in `execute`
+    jobs: Mutex<HashSet<u32>>,
+    notifiers: Notifiers,
+    /// Receiving end of `notifiers.tasks`, until the reaper takes it
+    tasks: Mutex<Option<mpsc::UnboundedReceiver<SessionTask>>>,
+    backend: Backend,
+    limits: SessionLimits,
+    recording: RecordingPolicy,
+    shell_integration: ShellIntegration,
+    redactor: Arc<Redactor>,
 }
 
 impl SessionManager {
-    pub fn new() -> Self {
+    pub fn new(
+        backend: Backend,
+        limits: SessionLimits,
+        recording: RecordingPolicy,
+        shell_integration: ShellIntegration,
+        redactor: Arc<Redactor>,
+    ) -> Self {
+        let (tasks_tx, tasks_rx) = mpsc::unbounded_channel();
         Self {
             sessions: Mutex::new(HashMap::new()),
+            reaped: Mutex::new(VecDeque::new()),
+            jobs: Mutex::new(HashSet::new()),
+            notifiers: Notifiers {
+                updates: broadcast::channel(UPDATE_CHANNEL_CAPACITY).0,
+                events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+                tasks: tasks_tx,
+            },
+            tasks: Mutex::new(Some(tasks_rx)),
+            backend,
+            limits,
+            recording,
+            shell_integration,
+            redactor,
+        }
+    }
+
+    /// Start a background task that periodically reaps sessions exceeding
+    /// the configured limits. Stops when the manager is dropped.
+    pub fn start_reaper(self: &Arc<Self>) {
+        let manager = Arc::downgrade(self);
+        let Some(mut tasks) = self.tasks.lock().unwrap().take() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REAP_INTERVAL);
+            loop {
+                let task = tokio::select! {
+                    _ = interval.tick() => None,
+                    Some(task) = tasks.recv() => Some(task),
+                };
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                match task {
+                    Some(SessionTask::Exited { session_id }) => manager.report_exit(session_id).await,
+                    Some(SessionTask::WatchAction { session_id, action }) => manager.run_watch_action(&session_id, action),
+                    // Reaping reads /proc and runs tmux, so keep it off the runtime
+                    None => {
+                        let _ = tokio::task::spawn_blocking(move || manager.reap()).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Run the input or signal action of a watch trigger
+    fn run_watch_action(&self, session_id: &str, action: WatchAction) {
+        let result = match &action {
+            WatchAction::SendInput { input } => self.send_input(session_id, input),
+            WatchAction::Signal { signal } => self.signal(session_id, signal),
+            WatchAction::Record | WatchAction::Notify => Ok(()),
+        };
+        match result {
+            Ok(()) => tracing::info!(session_id = %session_id, action = ?action, "Ran watch action"),
+            Err(e) => tracing::warn!(session_id = %session_id, action = ?action, "Watch action failed: {}", e),
+        }
+    }
+
+    /// Tell clients that a session's shell exited, once its exit code is
+    /// known. Sessions closed with `close_session` are not reported.
+    async fn report_exit(&self, session_id: String) {
+        let deadline = Instant::now() + EXIT_CODE_WAIT;
+        let exit_code = loop {
+            let exit_code = {
+                let mut sessions = self.sessions.lock().unwrap();
+                let Some(session) = sessions.get_mut(&session_id) else {
+                    return;
+                };
+                session.poll_usage();
+                session.exit.as_ref().map(|(code, _)| *code)
+            };
+            // The shell may close the PTY a moment before it can be reaped
+            if exit_code.is_some() || Instant::now() >= deadline {
+                break exit_code;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        tracing::info!(session_id = %session_id, exit_code = ?exit_code, "Session exited");
+        let _ = self.notifiers.events.send(SessionEvent {
+            session_id,
+            kind: SessionEventKind::Exited { exit_code },
+        });
+    }
+
+    /// Remove sessions that are idle, too old, or exited past retention.
+    /// Only taking them out holds the sessions lock; the rest blocks on
+    /// /proc and tmux.
+    fn reap(&self) {
+        let expired: Vec<(String, ReapReason, Session)> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let expired: Vec<(String, ReapReason)> = sessions
+                .iter()
+                .filter_map(|(id, s)| s.reap_reason(&self.limits).map(|reason| (id.clone(), reason)))
+                .collect();
+            expired
+                .into_iter()
+                .filter_map(|(id, reason)| sessions.remove(&id).map(|session| (id, reason, session)))
+                .collect()
+        };
+
+        for (session_id, reason, mut session) in expired {
+            tracing::info!(session_id = %session_id, reason = %reason, "Reaping session");
+            let mut info = session.info(&session_id);
+            info.is_alive = false;
+            info.reap_reason = Some(reason);
+            info.reaped_at = Some(Utc::now());
+            self.reaped.lock().unwrap().push_back(info);
+
+            // After the tombstone is in place, so listeners can still look
+            // the session up
+            let _ = self.notifiers.events.send(SessionEvent {
+                session_id: session_id.clone(),
+                kind: SessionEventKind::Reaped { reason },
+            });
+            let _ = self.notifiers.updates.send(session_id.clone());
+
+            if let Host::Tmux(tmux) = &session.inner.host {
+                tmux.kill();
+            }
+            shell_integration::remove_env_file(&session_id);
+        }
+
+        let mut reaped = self.reaped.lock().unwrap();
+        let cutoff = Utc::now() - TOMBSTONE_RETENTION;
+        reaped.retain(|info| info.reaped_at.is_some_and(|t| t > cutoff));
+        while reaped.len() > MAX_TOMBSTONES {
+            reaped.pop_front();
+        }
+    }
+
+    /// Error for an unknown session ID, mentioning why it was reaped if it was
+    fn not_found(&self, session_id: &str) -> String {
+        let reaped = self.reaped.lock().unwrap();
+        match reaped.iter().rev().find(|info| info.session_id == session_id) {
+            Some(info) => format!(
+                "Session {} was reaped ({})",
+                session_id,
+                info.reap_reason.map(|r| r.to_string()).unwrap_or_default()
+            ),
+            None => format!("Session {} not found", session_id),
         }
     }
 
+    /// ID of the session `name_or_id` refers to for `caller`: a session ID
+    /// as is, or the name of a session the caller may use — their own
+    /// before shared ones, live before exited, then recently reaped ones.
+    /// Anything else is returned unchanged, to be reported as not found.
+    pub fn resolve(&self, name_or_id: &str, caller: Option<&str>) -> String {
+        self.resolve_name(name_or_id, caller, false)
+    }
+
+    /// Like `resolve`, but names only refer to the caller's own sessions.
+    /// Names are unique per owner only, so tools that change a session use
+    /// this to never pick another client's shared session by mistake.
+    pub fn resolve_own(&self, name_or_id: &str, caller: Option<&str>) -> String {
+        self.resolve_name(name_or_id, caller, true)
+    }
+
+    fn resolve_name(&self, name_or_id: &str, caller: Option<&str>, own_only: bool) -> String {
+        let usable = |owner: Option<&str>, shared_with: &[String]| {
+            if own_only {
+                owner == caller
+            } else {
+                can_access(owner, shared_with, caller)
+            }
+        };
+        let sessions = self.sessions.lock().unwrap();
+        if sessions.contains_key(name_or_id) {
+            return name_or_id.to_string();
+        }
+        let named = sessions
+            .iter()
+            .filter(|(_, s)| s.name.as_deref() == Some(name_or_id) && usable(s.owner.as_deref(), &s.shared_with))
+            .min_by_key(|(_, s)| (s.owner.as_deref() != caller, !s.is_alive(), std::cmp::Reverse(s.created_at)));
+        if let Some((id, _)) = named {
+            return id.clone();
+        }
+        let reaped = self.reaped.lock().unwrap();
+        reaped
+            .iter()
+            .rev()
+            .find(|info| {
+                info.name.as_deref() == Some(name_or_id) && usable(info.owner.as_deref(), &info.shared_with)
+            })
+            .map_or_else(|| name_or_id.to_string(), |info| info.session_id.clone())
+    }
+
+    /// ID of the live session named `name` that belongs to `owner`
+    pub fn named_session(&self, name: &str, owner: Option<&str>) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        find_named(&sessions, name, owner).cloned()
+    }
+
+    /// Check the session limits before creating a session in `project`
+    fn check_limits(&self, sessions: &HashMap<String, Session>, project: Option<&str>) -> Result<(), String> {
+        let alive = || sessions.values().filter(|s| s.is_alive());
+
+        if let Some(max) = self.limits.max_sessions {
+            if alive().count() >= max {
+                tracing::warn!(max, "Denied session: session limit reached");
+                return Err(format!(
+                    "Session limit reached ({} live sessions). Close unused sessions first.",
+                    max
+                ));
+            }
+        }
+        if let (Some(max), Some(project)) = (self.limits.max_sessions_per_project, project) {
+            if alive().filter(|s| s.project.as_deref() == Some(project)).count() >= max {
+                tracing::warn!(project = %project, max, "Denied session: project session limit reached");
+                return Err(format!(
+                    "Session limit reached for project {} ({} live sessions). Close unused sessions first.",
+                    project, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Create a new interactive terminal session
-    pub fn create_session(
+    pub fn create_session(&self, new: NewSession) -> Result<String, String> {
+        self.create(new, None)
+    }
+
+    /// Create a session with the same shell, current directory, exported
+    /// environment and project as an existing one. Returns its ID and
+    /// whether the environment could be copied, which needs bash or zsh
+    /// with shell integration.
+    pub fn fork_session(
         &self,
-        cwd: Option<String>,
-        shell: Option<String>,
-        project: Option<String>,
-    ) -> Result<String, String> {
-        let pty_system = native_pty_system();
+        session_id: &str,
+        owner: Option<String>,
+        name: Option<String>,
+    ) -> Result<(String, bool), String> {
+        let (new, fork_env) = {
+            let sessions = self.sessions.lock().unwrap();
+            let session = sessions
+                .get(session_id)
+                .ok_or_else(|| self.not_found(session_id))?;
+            if !session.is_alive() {
+                return Err(format!("Session {} has exited", session_id));
+            }
+            let fork_env = shell_integration::env_file(session_id)
+                .ok()
+                .filter(|path| std::fs::metadata(path).is_ok_and(|m| m.len() > 0));
+            let new = NewSession {
+                cwd: Some(
+                    session
+                        .current_cwd(session.foreground_group())
+                        .unwrap_or_else(|| session.cwd.clone()),
+                ),
+                shell: Some(session.shell.clone()),
+                project: session.project.clone(),
+                labels: session.labels.clone(),
+                description: session.description.clone(),
+                owner,
+                name,
+                ..Default::default()
+            };
+            (new, fork_env)
+        };
+        let env_copied = fork_env.is_some();
+        let forked = self.create(new, fork_env)?;
+        tracing::info!(session_id = %session_id, forked = %forked, "Forked session");
+        Ok((forked, env_copied))
+    }
 
-        let pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 200,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| format!("Failed to open PTY: {}", e))?;
+    /// Create a session, starting its shell with the exported variables in
+    /// `fork_env` if given
+    fn create(&self, new: NewSession, fork_env: Option<PathBuf>) -> Result<String, String> {
+        let NewSession {
+            cwd,
+            shell,
+            project,
+            owner,
+            name,
+            labels,
+            description,
+            watch,
+            record,
+        } = new;
+        validate_labels(&labels)?;
+        if let Some(description) = &description {
+            validate_description(description)?;
+        }
+        if let Some(name) = &name {
+            validate_name(name)?;
+            let sessions = self.sessions.lock().unwrap();
+            if let Some(id) = find_named(&sessions, name, owner.as_deref()) {
+                return Err(format!("Session name {} is already used by session {}", name, id));
+            }
+        }
+        let mut watchers = Watchers::default();
+        for pattern in &watch {
+            watchers.add(pattern, WatchAction::Notify, false)?;
+        }
+        // Fail early; checked again once the shell has started
+        self.check_limits(&self.sessions.lock().unwrap(), project.as_deref())?;
 
         let shell_cmd = shell.unwrap_or_else(|| {
             std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
@@ -83,93 +696,179 @@ impl SessionManager {
                 .unwrap_or_else(|_| "/tmp".to_string())
         });
 
-        let mut cmd = CommandBuilder::new(&shell_cmd);
-        cmd.cwd(&working_dir);
-        // Disable pager to prevent hangs in non-fully-functional PTY
-        cmd.env("PAGER", "cat");
-        cmd.env("GIT_PAGER", "cat");
-
-        // Spawn the shell in the slave PTY
-        let _child = pair
-            .slave
-            .spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn shell: {}", e))?;
-
-        // Drop the slave — we only need the master side
-        drop(pair.slave);
-
-        let writer = pair
-            .master
-            .take_writer()
-            .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
-
-        let mut reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
+        let session_id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
+        let started = Instant::now();
 
-        let output: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
-        let is_alive = Arc::new(Mutex::new(true));
+        let recording_owner = RecordingOwner {
+            owner: owner.clone(),
+            project: project.clone(),
+        };
+        let mut recording = Recording::new(
+            self.recording.path(&session_id),
+            SCREEN_COLS,
+            SCREEN_ROWS,
+            shell_cmd.clone(),
+            recording_owner,
+        );
+        recording.set_enabled(record.unwrap_or(self.recording.record_by_default))?;
 
-        // Spawn a background thread to continuously read PTY output
-        let output_clone = Arc::clone(&output);
-        let alive_clone = Arc::clone(&is_alive);
-        let reader_handle = std::thread::spawn(move || {
-            let mut buf = [0u8; 4096];
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => {
-                        // EOF — process exited
-                        *alive_clone.lock().unwrap() = false;
-                        break;
-                    }
-                    Ok(n) => {
-                        let mut output = output_clone.lock().unwrap();
-                        output.extend_from_slice(&buf[..n]);
-                        // Trim if over max size — keep the tail
-                        if output.len() > MAX_BUFFER_SIZE {
-                            let drain_to = output.len() - MAX_BUFFER_SIZE;
-                            output.drain(..drain_to);
-                        }
-                    }
-                    Err(_) => {
-                        *alive_clone.lock().unwrap() = false;
-                        break;
-                    }
+        let injection = self
+            .shell_integration
+            .inject(&shell_cmd, &session_id, fork_env.as_deref())?;
+        let spawned = match self.backend {
+            Backend::Pty => spawn_pty(&shell_cmd, &working_dir, &injection)?,
+            Backend::Tmux => {
+                let meta = TmuxMeta {
+                    session_id: session_id.clone(),
+                    name: name.clone(),
+                    project: project.clone(),
+                    labels: labels.clone(),
+                    description: description.clone(),
+                    cwd: working_dir.clone(),
+                    shell: shell_cmd.clone(),
+                    created_at,
+                    owner: owner.clone(),
+                    shared_with: Vec::new(),
+                };
+                let env: Vec<(&str, &str)> = SHELL_ENV
+                    .iter()
+                    .copied()
+                    .chain(injection.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                    .collect();
+                let tmux = TmuxSession::spawn(&meta, &injection.args, &env)?;
+                Spawned {
+                    pid: tmux.pid(),
+                    reader: Box::new(tmux.reader(0)?),
+                    writer: Box::new(tmux.writer()),
+                    host: Host::Tmux(tmux),
                 }
             }
-        });
-
-        let session_id = Uuid::new_v4().to_string();
+        };
 
         let session = Session {
-            inner: SessionInner {
-                _master: pair.master,
-                writer,
-                output,
-                is_alive,
-                _reader_handle: reader_handle,
-            },
+            inner: start_session(&session_id, spawned, watchers, recording, self.notifiers.clone()),
+            name,
             project,
+            labels,
+            description,
+            owner,
+            shared_with: Vec::new(),
+            shell: shell_cmd,
             cwd: working_dir,
-            created_at: Utc::now(),
+            created_at,
+            started,
+            last_usage: None,
+            exit: None,
         };
 
-        self.sessions
-            .lock()
-            .unwrap()
-            .insert(session_id.clone(), session);
+        let mut sessions = self.sessions.lock().unwrap();
+        // Other sessions may have taken the name or reached the limits
+        // while the shell started
+        let taken = session.name.as_deref().and_then(|name| {
+            find_named(&sessions, name, session.owner.as_deref())
+                .map(|id| format!("Session name {} is already used by session {}", name, id))
+        });
+        let checked = match taken {
+            Some(error) => Err(error),
+            None => self.check_limits(&sessions, session.project.as_deref()),
+        };
+        if let Err(error) = checked {
+            // Dropping a PTY session kills its shell; tmux ones need killing
+            if let Host::Tmux(tmux) = &session.inner.host {
+                tmux.kill();
+            }
+            shell_integration::remove_env_file(&session_id);
+            return Err(error);
+        }
+        sessions.insert(session_id.clone(), session);
 
         Ok(session_id)
     }
 
+    /// Reattach to sessions left running by a previous server process.
+    /// Only applies to the tmux backend. Returns the number of sessions restored.
+    pub fn reattach(&self) -> usize {
+        if self.backend != Backend::Tmux {
+            return 0;
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let mut restored = 0;
+        for meta in TmuxSession::list() {
+            if sessions.contains_key(&meta.session_id) {
+                continue;
+            }
+            let tmux = match TmuxSession::for_id(&meta.session_id) {
+                Ok(tmux) => tmux,
+                Err(e) => {
+                    tracing::warn!(session_id = %meta.session_id, "Failed to reattach session: {}", e);
+                    continue;
+                }
+            };
+            // Replay recent output so nothing printed while we were away is lost
+            let reader = match tmux.reader(MAX_BUFFER_SIZE as u64) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    tracing::warn!(session_id = %meta.session_id, "Failed to reattach session: {}", e);
+                    continue;
+                }
+            };
+            let spawned = Spawned {
+                pid: tmux.pid(),
+                reader: Box::new(reader),
+                writer: Box::new(tmux.writer()),
+                host: Host::Tmux(tmux),
+            };
+            let age = (Utc::now() - meta.created_at).to_std().unwrap_or_default();
+            // Continue an existing recording; the replayed output is recorded again
+            let path = self.recording.path(&meta.session_id);
+            let recording_owner = RecordingOwner {
+                owner: meta.owner.clone(),
+                project: meta.project.clone(),
+            };
+            let mut recording = Recording::new(path.clone(), SCREEN_COLS, SCREEN_ROWS, meta.shell.clone(), recording_owner);
+            if self.recording.record_by_default || path.exists() {
+                if let Err(e) = recording.set_enabled(true) {
+                    tracing::warn!(session_id = %meta.session_id, "Failed to resume recording: {}", e);
+                }
+            }
+
+            let session = Session {
+                inner: start_session(&meta.session_id, spawned, Watchers::default(), recording, self.notifiers.clone()),
+                name: meta.name,
+                project: meta.project,
+                labels: meta.labels,
+                description: meta.description,
+                owner: meta.owner,
+                shared_with: meta.shared_with,
+                shell: meta.shell,
+                cwd: meta.cwd,
+                created_at: meta.created_at,
+                started: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                last_usage: None,
+                exit: None,
+            };
+            tracing::info!(session_id = %meta.session_id, "Reattached session");
+            sessions.insert(meta.session_id, session);
+            restored += 1;
+        }
+        restored
+    }
+
     /// Send input text to a session
     pub fn send_input(&self, session_id: &str, input: &str) -> Result<(), String> {
         let mut sessions = self.sessions.lock().unwrap();
         let session = sessions
             .get_mut(session_id)
-            .ok_or_else(|| format!("Session {} not found", session_id))?;
+            .ok_or_else(|| self.not_found(session_id))?;
 
+        // Where the output of this input starts, taken before the echo can
+        // arrive
+        let (offset, integrated) = {
+            let shell = session.inner.shell.lock().unwrap();
+            (shell.offset(), shell.state().is_some())
+        };
         session
             .inner
             .writer
@@ -182,15 +881,20 @@ impl SessionManager {
             .flush()
             .map_err(|e| format!("Failed to flush PTY: {}", e))?;
 
+        // Only input that reached the terminal is recorded
+        session.inner.recording.lock().unwrap().input(input);
+        session.inner.history.lock().unwrap().input(input, offset, integrated);
+        *session.inner.last_activity.lock().unwrap() = Instant::now();
+
         Ok(())
     }
 
     /// Read and drain accumulated output from a session
-    pub fn read_output(&self, session_id: &str, max_lines: Option<u32>) -> Result<(String, bool), String> {
+    pub fn read_output(&self, session_id: &str, max_lines: Option<u32>) -> Result<SessionOutput, String> {
         let sessions = self.sessions.lock().unwrap();
         let session = sessions
             .get(session_id)
-            .ok_or_else(|| format!("Session {} not found", session_id))?;
+            .ok_or_else(|| self.not_found(session_id))?;
 
         let mut output_buf = session.inner.output.lock().unwrap();
         let is_alive = *session.inner.is_alive.lock().unwrap();
@@ -201,49 +905,531 @@ impl SessionManager {
         // Strip ANSI escape sequences for cleaner output
         let cleaned = strip_ansi_escapes(&text);
 
-        // Optionally limit lines
-        let result = if let Some(max) = max_lines {
-            let lines: Vec<&str> = cleaned.lines().collect();
+        // Redact secrets before the output leaves the server. All of it, so
+        // secrets spanning lines are caught even if cut off below.
+        let (redacted, redactions) = self.redactor.redact(&cleaned);
+
+        // Optionally limit lines, and only report the secrets in those
+        let (result, redactions) = if let Some(max) = max_lines {
+            let lines: Vec<&str> = redacted.lines().collect();
             let start = lines.len().saturating_sub(max as usize);
-            lines[start..].join("\n")
+            let result = lines[start..].join("\n");
+            let redactions = self.redactor.marked(&result);
+            (result, redactions)
+        } else {
+            (redacted, redactions)
+        };
+
+        Ok(SessionOutput {
+            output: result,
+            is_alive,
+            redactions,
+        })
+    }
+
+    /// Current working directory of a session, if it exists
+    pub fn session_cwd(&self, session_id: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(session_id)?;
+        Some(
+            session
+                .current_cwd(session.foreground_group())
+                .unwrap_or_else(|| session.cwd.clone()),
+        )
+    }
+
+    /// Project of a session, if it exists
+    pub fn session_project(&self, session_id: &str) -> Option<Option<String>> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(session_id).map(|s| s.project.clone())
+    }
+
+    /// Current screen of a session as plain text, with secrets redacted
+    pub fn screen(&self, session_id: &str) -> Result<String, String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let contents = session.inner.screen.lock().unwrap().screen().contents();
+        Ok(self.redactor.redact(&contents).0)
+    }
+
+    /// Recent output of a session, including output already returned by
+    /// `read_output`, with ANSI escapes stripped and secrets redacted
+    pub fn scrollback(&self, session_id: &str) -> Result<String, String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let raw = session.inner.scrollback.lock().unwrap();
+        let text = strip_ansi_escapes(&String::from_utf8_lossy(&raw));
+        Ok(self.redactor.redact(&text).0)
+    }
+
+    /// Status of a single session `caller` may use, including recently
+    /// reaped ones
+    pub fn session_info(&self, session_id: &str, caller: Option<&str>) -> Result<SessionInfo, String> {
+        let denied = || {
+            format!(
+                "Session {} belongs to another client; ask its owner to share it",
+                session_id
+            )
+        };
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(session_id) {
+            if !session.accessible_by(caller) {
+                return Err(denied());
+            }
+            return Ok(session.info(session_id));
+        }
+        let reaped = self.reaped.lock().unwrap();
+        match reaped.iter().rev().find(|info| info.session_id == session_id) {
+            Some(info) if can_access(info.owner.as_deref(), &info.shared_with, caller) => Ok(info.clone()),
+            Some(_) => Err(denied()),
+            None => Err(format!("Session {} not found", session_id)),
+        }
+    }
+
+    /// Receive the ID of a session each time it produces output, exits, or
+    /// is closed or reaped
+    pub fn subscribe_updates(&self) -> broadcast::Receiver<String> {
+        self.notifiers.updates.subscribe()
+    }
+
+    /// Receive exits, watch pattern matches and reaps of all sessions
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SessionEvent> {
+        self.notifiers.events.subscribe()
+    }
+
+    /// Add a trigger on a session's output. Returns the trigger ID.
+    pub fn watch_session(&self, session_id: &str, pattern: &str, action: WatchAction, once: bool) -> Result<u32, String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let mut watchers = session.inner.watchers.lock().unwrap();
+        watchers.add(pattern, action, once)
+    }
+
+    /// Remove a trigger from a session
+    pub fn unwatch_session(&self, session_id: &str, trigger_id: u32) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        if session.inner.watchers.lock().unwrap().remove(trigger_id) {
+            Ok(())
         } else {
-            cleaned
+            Err(format!("Session {} has no trigger {}", session_id, trigger_id))
+        }
+    }
+
+    /// Triggers of a session and their hits after `after_hit_id`, with
+    /// secrets redacted
+    pub fn watch_hits(
+        &self,
+        session_id: &str,
+        after_hit_id: Option<u64>,
+        trigger_id: Option<u32>,
+    ) -> Result<(Vec<TriggerInfo>, Vec<WatchHit>), String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let watchers = session.inner.watchers.lock().unwrap();
+        let mut hits = watchers.hits(after_hit_id, trigger_id);
+        for hit in &mut hits {
+            hit.line = self.redactor.redact(&hit.line).0;
+        }
+        Ok((watchers.triggers(), hits))
+    }
+
+    /// Start or pause recording a session
+    pub fn set_recording(&self, session_id: &str, enabled: bool) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let mut recording = session.inner.recording.lock().unwrap();
+        recording.set_enabled(enabled)
+    }
+
+    /// Project of a session whose recording `caller` may export. Recordings
+    /// outlive their sessions, so once a session is closed or its tombstone
+    /// is gone, the owner and project kept with the recording are checked.
+    pub fn recording_project(&self, session_id: &str, caller: Option<&str>) -> Result<Option<String>, String> {
+        let known = self.sessions.lock().unwrap().contains_key(session_id)
+            || self.reaped.lock().unwrap().iter().any(|info| info.session_id == session_id);
+        if known {
+            return self.session_info(session_id, caller).map(|info| info.project);
+        }
+        match recording::owner(&self.recording_path(session_id)?) {
+            Some(kept) if can_access(kept.owner.as_deref(), &[], caller) => Ok(kept.project),
+            Some(_) => Err(format!(
+                "Session {} belongs to another client; ask its owner to share it",
+                session_id
+            )),
+            // Only stdio may export recordings that were kept without an owner
+            None if caller.is_none() => Ok(None),
+            None => Err(format!("Session {} not found", session_id)),
+        }
+    }
+
+    /// Path of a session's recording, refusing IDs that could reach outside
+    /// the recording directory
+    fn recording_path(&self, session_id: &str) -> Result<std::path::PathBuf, String> {
+        if Uuid::parse_str(session_id).is_err() {
+            return Err(format!("Session {} not found", session_id));
+        }
+        Ok(self.recording.path(session_id))
+    }
+
+    /// A session's recording in asciicast v2 format with secrets redacted.
+    /// Recordings outlive their sessions, so this doesn't check that the
+    /// session still exists. Unless `to` is given, recordings larger than
+    /// `MAX_INLINE_EXPORT` are refused.
+    pub fn export_recording(&self, session_id: &str, to: Option<&str>) -> Result<String, String> {
+        let path = self.recording_path(session_id)?;
+        let size = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return Err(format!("Session {} has no recording", session_id)),
+        };
+        if to.is_none() && size > recording::MAX_INLINE_EXPORT {
+            return Err(format!(
+                "Recording of session {} is {} bytes; pass a path to export it to a file",
+                session_id, size
+            ));
+        }
+        let exported = recording::export(&path, &self.redactor)?;
+        if let Some(to) = to {
+            std::fs::write(to, &exported).map_err(|e| format!("Failed to write {}: {}", to, e))?;
+        }
+        Ok(exported)
+    }
+
+    /// A session's output, including output already returned by
+    /// `read_output`, rendered as a transcript with secrets redacted.
+    /// Written to `to` if given; otherwise transcripts larger than
+    /// `MAX_INLINE_EXPORT` are refused.
+    pub fn export_transcript(&self, session_id: &str, format: transcript::Format, to: Option<&str>) -> Result<String, String> {
+        let (info, raw) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let session = sessions
+                .get_mut(session_id)
+                .ok_or_else(|| self.not_found(session_id))?;
+            let raw = session.inner.scrollback.lock().unwrap().clone();
+            (session.info(session_id), raw)
+        };
+        let rendered = transcript::render(&info, &String::from_utf8_lossy(&raw), format, &self.redactor);
+        match to {
+            Some(to) => std::fs::write(to, &rendered).map_err(|e| format!("Failed to write {}: {}", to, e))?,
+            None if rendered.len() as u64 > recording::MAX_INLINE_EXPORT => {
+                return Err(format!(
+                    "Transcript of session {} is {} bytes; pass a path to export it to a file",
+                    session_id,
+                    rendered.len()
+                ));
+            }
+            None => {}
+        }
+        Ok(rendered)
+    }
+
+    /// Commands run in a session after `after_id`, at most `limit` of the
+    /// newest, with secrets redacted
+    pub fn history(&self, session_id: &str, after_id: Option<u64>, limit: usize) -> Result<Vec<history::Command>, String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let mut commands = session.inner.history.lock().unwrap().list(after_id, limit);
+        for command in &mut commands {
+            command.input = self.redactor.redact(&command.input).0;
+        }
+        Ok(commands)
+    }
+
+    /// A command run in a session and its output (the last `lines` lines,
+    /// if given), ANSI escapes stripped and secrets redacted. The output is
+    /// cut at the front if it no longer fits in the scrollback; the flag
+    /// says whether it was.
+    pub fn history_command(
+        &self,
+        session_id: &str,
+        command_id: u64,
+        lines: Option<u32>,
+    ) -> Result<(history::Command, String, bool), String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let mut command = session
+            .inner
+            .history
+            .lock()
+            .unwrap()
+            .get(command_id)
+            .ok_or_else(|| format!("Session {} has no command {}", session_id, command_id))?;
+
+        let (raw, truncated) = {
+            let scrollback = session.inner.scrollback.lock().unwrap();
+            let end_offset = session.inner.shell.lock().unwrap().offset();
+            let retained_from = end_offset - scrollback.len() as u64;
+            let start = command.output_start.max(retained_from);
+            let end = command.output_end.unwrap_or(end_offset).clamp(start, end_offset);
+            let raw = scrollback[(start - retained_from) as usize..(end - retained_from) as usize].to_vec();
+            (raw, start > command.output_start)
         };
+        let text = strip_ansi_escapes(&String::from_utf8_lossy(&raw));
+        let text = text.replace("\r\n", "\n");
+        let text = match lines {
+            Some(n) => {
+                let all: Vec<&str> = text.lines().collect();
+                all[all.len().saturating_sub(n as usize)..].join("\n")
+            }
+            None => text,
+        };
+        command.input = self.redactor.redact(&command.input).0;
+        Ok((command, self.redactor.redact(&text).0, truncated))
+    }
 
-        Ok((result, is_alive))
+    /// Send a signal, given by name, to every process in a session
+    pub fn signal(&self, session_id: &str, signal: &str) -> Result<(), String> {
+        let signal = watch::signal_number(signal)?;
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+        let pid = session
+            .inner
+            .pid
+            .ok_or_else(|| format!("Session {} has no known process", session_id))?;
+        if !session.is_alive() {
+            return Err(format!("Session {} has exited", session_id));
+        }
+        process::signal_session(pid, signal);
+        Ok(())
     }
 
     /// Close and remove a session
     pub fn close_session(&self, session_id: &str) -> Result<(), String> {
         let mut sessions = self.sessions.lock().unwrap();
-        let _session = sessions
+        let session = sessions
             .remove(session_id)
-            .ok_or_else(|| format!("Session {} not found", session_id))?;
+            .ok_or_else(|| self.not_found(session_id))?;
 
-        // Dropping the session will close the PTY master, which kills the child process
+        // Dropping a PTY session closes the master, which kills the child
+        // process; tmux sessions have to be killed explicitly
+        if let Host::Tmux(tmux) = &session.inner.host {
+            tmux.kill();
+        }
+        shell_integration::remove_env_file(session_id);
+        drop(sessions);
+        let _ = self.notifiers.updates.send(session_id.to_string());
         Ok(())
     }
 
-    /// List all sessions, optionally filtered by project
-    pub fn list_sessions(&self, project: Option<&str>) -> Vec<SessionInfo> {
+    /// Whether a session is still open, rather than closed or reaped
+    pub fn is_open(&self, session_id: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(session_id)
+    }
+
+    /// List the sessions `caller` may use, optionally filtered by project.
+    /// Recently reaped sessions are included with their reap reason.
+    pub fn list_sessions(&self, project: Option<&str>, caller: Option<&str>) -> Vec<SessionInfo> {
+        let matches = |p: &Option<String>| project.is_none() || p.as_deref() == project;
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let mut list: Vec<SessionInfo> = sessions
+            .iter_mut()
+            .filter(|(_, s)| matches(&s.project) && s.accessible_by(caller))
+            .map(|(id, s)| s.info(id))
+            .collect();
+
+        let reaped = self.reaped.lock().unwrap();
+        list.extend(
+            reaped
+                .iter()
+                .filter(|info| matches(&info.project))
+                .filter(|info| can_access(info.owner.as_deref(), &info.shared_with, caller))
+                .cloned(),
+        );
+        list
+    }
+
+    /// Check that `caller` owns the session or has it shared with them.
+    /// Unknown sessions pass, so the caller reports them as not found.
+    pub fn check_access(&self, session_id: &str, caller: Option<&str>) -> Result<(), String> {
         let sessions = self.sessions.lock().unwrap();
-        sessions
-            .iter()
-            .filter(|(_, s)| {
-                if let Some(proj) = project {
-                    s.project.as_deref() == Some(proj)
-                } else {
-                    true
-                }
-            })
-            .map(|(id, s)| SessionInfo {
-                session_id: id.clone(),
-                project: s.project.clone(),
-                cwd: s.cwd.clone(),
-                is_alive: *s.inner.is_alive.lock().unwrap(),
-                created_at: s.created_at,
-            })
-            .collect()
+        match sessions.get(session_id) {
+            Some(session) if !session.accessible_by(caller) => {
+                tracing::warn!(session_id = %session_id, caller = ?caller, "Denied access to another client's session");
+                Err(format!(
+                    "Session {} belongs to another client; ask its owner to share it",
+                    session_id
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Set (or with `None`, remove) labels of a session, and replace its
+    /// description if given; an empty description removes it
+    pub fn update_session(
+        &self,
+        session_id: &str,
+        labels: HashMap<String, Option<String>>,
+        description: Option<String>,
+    ) -> Result<SessionInfo, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+
+        let mut updated = session.labels.clone();
+        for (key, value) in labels {
+            match value {
+                Some(value) => updated.insert(key, value),
+                None => updated.remove(&key),
+            };
+        }
+        validate_labels(&updated)?;
+        let description = match description {
+            Some(description) if description.is_empty() => None,
+            Some(description) => {
+                validate_description(&description)?;
+                Some(description)
+            }
+            None => session.description.clone(),
+        };
+
+        if let Host::Tmux(tmux) = &session.inner.host {
+            tmux.set_metadata(&updated, description.as_deref())?;
+        }
+        session.labels = updated;
+        session.description = description;
+        Ok(session.info(session_id))
+    }
+
+    /// Grant `client` (or `*` for everyone) access to a session, or revoke
+    /// it. Only the owner may change who a session is shared with. Returns
+    /// the clients the session is now shared with.
+    pub fn share_session(
+        &self,
+        session_id: &str,
+        caller: Option<&str>,
+        client: &str,
+        revoke: bool,
+    ) -> Result<Vec<String>, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| self.not_found(session_id))?;
+
+        if caller.is_some() && session.owner.is_some() && session.owner.as_deref() != caller {
+            return Err(format!("Only the owner of session {} may share it", session_id));
+        }
+        if session.owner.is_none() {
+            return Err(format!("Session {} has no owner and is open to every client", session_id));
+        }
+
+        if revoke {
+            session.shared_with.retain(|c| c != client);
+        } else if !session.shared_with.iter().any(|c| c == client) {
+            session.shared_with.push(client.to_string());
+        }
+        if let Host::Tmux(tmux) = &session.inner.host {
+            tmux.set_shared_with(&session.shared_with)?;
+        }
+        Ok(session.shared_with.clone())
+    }
+
+    /// Stop every PTY session and running command: send SIGHUP and SIGTERM
+    /// to all their processes, force-kill whatever is left after the grace
+    /// period, and optionally write each session's unread output to disk.
+    /// tmux sessions are left running so they can be reattached.
+    pub fn shutdown(&self, policy: &ShutdownPolicy) {
+        let mut sessions = std::mem::take(&mut *self.sessions.lock().unwrap());
+        let jobs: Vec<u32> = self.jobs.lock().unwrap().iter().copied().collect();
+
+        let shells: Vec<u32> = sessions
+            .values()
+            .filter(|s| matches!(s.inner.host, Host::Pty { .. }))
+            .filter_map(|s| s.inner.pid)
+            .collect();
+        let leaders: Vec<u32> = shells.iter().chain(&jobs).copied().collect();
+        if !leaders.is_empty() {
+            tracing::info!(
+                sessions = shells.len(),
+                jobs = jobs.len(),
+                "Terminating sessions and running commands"
+            );
+        }
+
+        // Interactive shells ignore SIGTERM but exit on SIGHUP, like on a
+        // terminal hangup
+        for &sid in &leaders {
+            process::signal_session(sid, libc::SIGHUP);
+            process::signal_session(sid, libc::SIGTERM);
+        }
+
+        let deadline = Instant::now() + policy.grace;
+        let mut remaining = leaders;
+        loop {
+            // Reap exited shells; execute reaps its own commands
+            for session in sessions.values_mut() {
+                session.poll_usage();
+            }
+            remaining.retain(|&sid| process::session_alive(sid));
+            if remaining.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        for &sid in &remaining {
+            tracing::warn!(pid = sid, "Force-killing processes that outlived the grace period");
+            process::signal_session(sid, libc::SIGKILL);
+        }
+        if !remaining.is_empty() {
+            // Give the reader threads a moment to drain the final output
+            std::thread::sleep(Duration::from_millis(100));
+            for session in sessions.values_mut() {
+                session.poll_usage();
+            }
+        }
+
+        if let Some(dir) = &policy.transcript_dir {
+            self.write_transcripts(dir, &sessions);
+        }
+        // tmux sessions live on and keep saving their environment
+        for (session_id, session) in &sessions {
+            if matches!(session.inner.host, Host::Pty { .. }) {
+                shell_integration::remove_env_file(session_id);
+            }
+        }
+    }
+
+    /// Write the unread output of each PTY session to `<dir>/<session_id>.txt`
+    fn write_transcripts(&self, dir: &std::path::Path, sessions: &HashMap<String, Session>) {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            tracing::error!("Failed to create transcript directory {}: {}", dir.display(), e);
+            return;
+        }
+        for (session_id, session) in sessions {
+            if !matches!(session.inner.host, Host::Pty { .. }) {
+                continue;
+            }
+            let raw = std::mem::take(&mut *session.inner.output.lock().unwrap());
+            if raw.is_empty() {
+                continue;
+            }
+            let text = strip_ansi_escapes(&String::from_utf8_lossy(&raw));
+            let (redacted, _) = self.redactor.redact(&text);
+            let path = dir.join(format!("{}.txt", session_id));
+            match std::fs::write(&path, redacted) {
+                Ok(()) => tracing::info!(session_id = %session_id, path = %path.display(), "Wrote session transcript"),
+                Err(e) => tracing::error!(session_id = %session_id, "Failed to write transcript: {}", e),
+            }
+        }
     }
 
     /// Execute a command synchronously — create a temporary PTY, run the command,
@@ -258,8 +1444,8 @@ impl SessionManager {
 
         let pair = pty_system
             .openpty(PtySize {
-                rows: 24,
-                cols: 200,
+                rows: SCREEN_ROWS,
+                cols: SCREEN_COLS,
                 pixel_width: 0,
                 pixel_height: 0,
             })
@@ -280,20 +1466,30 @@ impl SessionManager {
         cmd.env("PAGER", "cat");
         cmd.env("GIT_PAGER", "cat");
 
+        let started = Instant::now();
         let mut child = pair
             .slave
             .spawn_command(cmd)
             .map_err(|e| format!("Failed to spawn command: {}", e))?;
-
+        let pid = child
+            .process_id()
+            .ok_or_else(|| "Failed to get process ID of command".to_string())?;
         drop(pair.slave);
 
         // Read output in a background thread
-        let mut reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| format!("Failed to get reader: {}", e))?;
+        let mut reader = match pair.master.try_clone_reader() {
+            Ok(reader) => reader,
+            Err(e) => {
+                // Don't leave the command running unwatched
+                let _ = child.kill();
+                let _ = process::wait_with_timeout(pid, started, started.elapsed() + Duration::from_secs(1));
+                return Err(format!("Failed to get reader: {}", e));
+            }
+        };
+        // Tracked only from here on, where every path removes it again
+        self.jobs.lock().unwrap().insert(pid);
 
-        let output: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let output: Arc<Mutex<CapturedOutput>> = Arc::default();
         let output_clone = Arc::clone(&output);
 
         let reader_thread = std::thread::spawn(move || {
@@ -301,15 +1497,7 @@ impl SessionManager {
             loop {
                 match reader.read(&mut buf) {
                     Ok(0) => break,
-                    Ok(n) => {
-                        let mut out = output_clone.lock().unwrap();
-                        out.extend_from_slice(&buf[..n]);
-                        // Hard cap at 2 MB for synchronous execution
-                        if out.len() > 2 * 1024 * 1024 {
-                            let drain_to = out.len() - 2 * 1024 * 1024;
-                            out.drain(..drain_to);
-                        }
-                    }
+                    Ok(n) => output_clone.lock().unwrap().push(&buf[..n]),
                     Err(_) => break,
                 }
             }
@@ -317,14 +1505,16 @@ impl SessionManager {
 
         // Wait for the child with optional timeout
         let timeout = Duration::from_secs(timeout_secs.unwrap_or(300));
-        let exit_code = match wait_with_timeout(&mut child, timeout) {
-            Ok(status) => status.exit_code(),
-            Err(e) => {
-                // Try to kill on timeout
-                let _ = child.kill();
-                return Err(format!("Command timed out after {}s: {}", timeout.as_secs(), e));
-            }
-        };
+        let waited = process::wait_with_timeout(pid, started, timeout);
+        if waited.is_err() {
+            tracing::warn!(command = %command, timeout_secs = timeout.as_secs(), "Command timed out");
+            // Try to kill on timeout, then reap so it doesn't linger as a zombie
+            let _ = child.kill();
+            let _ = process::wait_with_timeout(pid, started, timeout + Duration::from_secs(1));
+        }
+        self.jobs.lock().unwrap().remove(&pid);
+        let (exit_code, usage) = waited
+            .map_err(|e| format!("Command timed out after {}s: {}", timeout.as_secs(), e))?;
 
         // Wait for reader thread to finish
         let _ = reader_thread.join();
@@ -332,36 +1522,346 @@ impl SessionManager {
         // Drop the master to ensure reader thread exits
         drop(pair.master);
 
-        let raw_output = output.lock().unwrap();
-        let stdout = String::from_utf8_lossy(&raw_output).to_string();
+        let stdout = output.lock().unwrap().text();
         let cleaned = strip_ansi_escapes(&stdout);
+        let (redacted, redactions) = self.redactor.redact(&cleaned);
 
         Ok(ExecResult {
-            stdout: cleaned,
+            stdout: redacted,
             exit_code,
+            usage,
+            redactions,
         })
     }
 }
 
-/// Wait for child process with timeout using polling
-fn wait_with_timeout(
-    child: &mut Box<dyn portable_pty::Child + Send + Sync>,
-    timeout: Duration,
-) -> Result<portable_pty::ExitStatus, String> {
-    let start = std::time::Instant::now();
-    let poll_interval = Duration::from_millis(50);
-
-    loop {
-        match child.try_wait() {
-            Ok(Some(status)) => return Ok(status),
-            Ok(None) => {
-                if start.elapsed() >= timeout {
-                    return Err("Timeout".to_string());
+/// Output of an `execute` command: its start and end, and how much was
+/// dropped between them
+#[derive(Default)]
+struct CapturedOutput {
+    head: Vec<u8>,
+    tail: Vec<u8>,
+    dropped: usize,
+}
+
+impl CapturedOutput {
+    fn push(&mut self, data: &[u8]) {
+        let to_head = data.len().min(EXEC_HEAD_BYTES - self.head.len());
+        self.head.extend_from_slice(&data[..to_head]);
+        self.tail.extend_from_slice(&data[to_head..]);
+        if self.tail.len() > EXEC_TAIL_BYTES {
+            let drain_to = self.tail.len() - EXEC_TAIL_BYTES;
+            self.tail.drain(..drain_to);
+            self.dropped += drain_to;
+        }
+    }
+
+    /// The output, with a marker where the middle was dropped. The cut
+    /// ends at line breaks, so no partial line or escape sequence is left.
+    fn text(&self) -> String {
+        if self.dropped == 0 {
+            let mut raw = self.head.clone();
+            raw.extend_from_slice(&self.tail);
+            return String::from_utf8_lossy(&raw).into_owned();
+        }
+        let head_end = self.head.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let tail_start = self.tail.iter().position(|&b| b == b'\n').map_or(self.tail.len(), |i| i + 1);
+        let dropped = self.dropped + (self.head.len() - head_end) + tail_start;
+        format!(
+            "{}[... {} bytes omitted: execute keeps the first {} KB and last {} KB of output ...]\n{}",
+            String::from_utf8_lossy(&self.head[..head_end]),
+            dropped,
+            EXEC_HEAD_BYTES / 1024,
+            EXEC_TAIL_BYTES / 1024,
+            String::from_utf8_lossy(&self.tail[tail_start..])
+        )
+    }
+}
+
+/// Whether `caller` may use a session owned by `owner` and shared with
+/// `shared_with`. Unowned sessions and callers without an identity (stdio)
+/// are unrestricted.
+fn can_access(owner: Option<&str>, shared_with: &[String], caller: Option<&str>) -> bool {
+    let (Some(owner), Some(caller)) = (owner, caller) else {
+        return true;
+    };
+    owner == caller || shared_with.iter().any(|c| c == caller || c == "*")
+}
+
+/// A session to create
+#[derive(Default)]
+pub struct NewSession {
+    pub cwd: Option<String>,
+    pub shell: Option<String>,
+    pub project: Option<String>,
+    /// Client that creates the session
+    pub owner: Option<String>,
+    /// Name to refer to the session by, unique among its owner's live sessions
+    pub name: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub description: Option<String>,
+    /// Regexes to notify about when a line of output matches
+    pub watch: Vec<String>,
+    /// Whether to record the session, if not the recording policy's default
+    pub record: Option<bool>,
+}
+
+/// ID of the live session named `name` that belongs to `owner`
+fn find_named<'a>(sessions: &'a HashMap<String, Session>, name: &str, owner: Option<&str>) -> Option<&'a String> {
+    sessions
+        .iter()
+        .find(|(_, s)| s.name.as_deref() == Some(name) && s.owner.as_deref() == owner && s.is_alive())
+        .map(|(id, _)| id)
+}
+
+/// Check that label names are short and plain and values are short
+fn validate_labels(labels: &BTreeMap<String, String>) -> Result<(), String> {
+    for (key, value) in labels {
+        let plain = key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+        if key.is_empty() || key.len() > MAX_NAME_LEN || !plain {
+            return Err(format!(
+                "Invalid label name {:?}: use up to {} letters, digits, '-', '_', '.' or '/'",
+                key, MAX_NAME_LEN
+            ));
+        }
+        if value.len() > MAX_LABEL_VALUE_LEN || value.contains(',') {
+            return Err(format!(
+                "Invalid value of label {}: use up to {} characters, without ','",
+                key, MAX_LABEL_VALUE_LEN
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_description(description: &str) -> Result<(), String> {
+    if description.len() > MAX_DESCRIPTION_LEN {
+        return Err(format!("Description is longer than {} bytes", MAX_DESCRIPTION_LEN));
+    }
+    Ok(())
+}
+
+/// Check that a session name is short, plain and can't be mistaken for a
+/// session ID
+fn validate_name(name: &str) -> Result<(), String> {
+    let plain = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !plain {
+        return Err(format!(
+            "Invalid session name {:?}: use up to {} letters, digits, '-', '_' or '.'",
+            name, MAX_NAME_LEN
+        ));
+    }
+    if Uuid::parse_str(name).is_ok() {
+        return Err(format!("Invalid session name {}: it looks like a session ID", name));
+    }
+    Ok(())
+}
+
+/// A freshly spawned or reattached shell, before its reader thread starts
+struct Spawned {
+    host: Host,
+    pid: Option<u32>,
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+}
+
+/// Spawn a shell in a new PTY
+fn spawn_pty(shell: &str, cwd: &str, injection: &Injection) -> Result<Spawned, String> {
+    let pty_system = native_pty_system();
+
+    let pair = pty_system
+        .openpty(PtySize {
+            rows: SCREEN_ROWS,
+            cols: SCREEN_COLS,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| format!("Failed to open PTY: {}", e))?;
+
+    let mut cmd = CommandBuilder::new(shell);
+    cmd.args(&injection.args);
+    cmd.cwd(cwd);
+    for (key, value) in SHELL_ENV {
+        cmd.env(key, value);
+    }
+    for (key, value) in &injection.env {
+        cmd.env(key, value);
+    }
+
+    // Spawn the shell in the slave PTY
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+
+    // Drop the slave — we only need the master side
+    drop(pair.slave);
+
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
+
+    Ok(Spawned {
+        pid: child.process_id(),
+        reader,
+        writer,
+        host: Host::Pty {
+            master: pair.master,
+            _child: child,
+        },
+    })
+}
+
+/// Start the background thread that continuously reads a session's output,
+/// announcing each chunk, watch trigger hits and the exit via `notifiers`
+fn start_session(
+    session_id: &str,
+    spawned: Spawned,
+    watchers: Watchers,
+    recording: Recording,
+    notifiers: Notifiers,
+) -> SessionInner {
+    let Spawned {
+        host,
+        pid,
+        mut reader,
+        writer,
+    } = spawned;
+    let output: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+    let scrollback: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+    let screen = Arc::new(Mutex::new(vt100::Parser::new(SCREEN_ROWS, SCREEN_COLS, 0)));
+    let is_alive = Arc::new(Mutex::new(true));
+    let last_activity = Arc::new(Mutex::new(Instant::now()));
+    let watchers = Arc::new(Mutex::new(watchers));
+    let recording = Arc::new(Mutex::new(recording));
+    let shell = Arc::new(Mutex::new(ShellTracker::default()));
+    let history = Arc::new(Mutex::new(History::default()));
+
+    let output_clone = Arc::clone(&output);
+    let scrollback_clone = Arc::clone(&scrollback);
+    let screen_clone = Arc::clone(&screen);
+    let alive_clone = Arc::clone(&is_alive);
+    let activity_clone = Arc::clone(&last_activity);
+    let watchers_clone = Arc::clone(&watchers);
+    let recording_clone = Arc::clone(&recording);
+    let shell_clone = Arc::clone(&shell);
+    let history_clone = Arc::clone(&history);
+    let session_id = session_id.to_string();
+    let reader_handle = std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        let mut line = Vec::new();
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => {
+                    // EOF or error — process exited
+                    *alive_clone.lock().unwrap() = false;
+                    *activity_clone.lock().unwrap() = Instant::now();
+                    let _ = notifiers.updates.send(session_id.clone());
+                    let _ = notifiers.tasks.send(SessionTask::Exited { session_id });
+                    break;
+                }
+                Ok(n) => {
+                    *activity_clone.lock().unwrap() = Instant::now();
+                    append_capped(&mut output_clone.lock().unwrap(), &buf[..n]);
+                    // Offsets into the scrollback are taken from the tracker,
+                    // so update both together
+                    let marks = {
+                        let mut scrollback = scrollback_clone.lock().unwrap();
+                        append_capped(&mut scrollback, &buf[..n]);
+                        shell_clone.lock().unwrap().feed(&buf[..n])
+                    };
+                    for mark in marks {
+                        let mut history = history_clone.lock().unwrap();
+                        match mark {
+                            ShellMark::CommandStarted { echo, offset } => {
+                                history.started(&strip_ansi_escapes(&echo), offset)
+                            }
+                            ShellMark::CommandFinished { exit_code, offset } => history.finished(exit_code, offset),
+                        }
+                    }
+                    screen_clone.lock().unwrap().process(&buf[..n]);
+                    recording_clone.lock().unwrap().output(&buf[..n]);
+                    // Nobody listening is fine
+                    let _ = notifiers.updates.send(session_id.clone());
+                    match_watchers(&session_id, &mut watchers_clone.lock().unwrap(), &mut line, &buf[..n], &notifiers);
                 }
-                std::thread::sleep(poll_interval);
             }
-            Err(e) => return Err(format!("Wait error: {}", e)),
         }
+    });
+
+    SessionInner {
+        host,
+        pid,
+        writer,
+        output,
+        scrollback,
+        screen,
+        is_alive,
+        last_activity,
+        watchers,
+        recording,
+        shell,
+        history,
+        _reader_handle: reader_handle,
+    }
+}
+
+/// Match complete lines of output against the session's watch triggers
+/// and hand their actions on. `line` carries the unfinished last line over
+/// to the next chunk.
+fn match_watchers(session_id: &str, watchers: &mut Watchers, line: &mut Vec<u8>, data: &[u8], notifiers: &Notifiers) {
+    if watchers.is_empty() {
+        line.clear();
+        return;
+    }
+    line.extend_from_slice(data);
+    while let Some(end) = line.iter().position(|&b| b == b'\n') {
+        let text = strip_ansi_escapes(&String::from_utf8_lossy(&line[..end]));
+        let text = text.trim_end_matches('\r');
+        for fired in watchers.match_line(text) {
+            tracing::info!(session_id = %session_id, trigger_id = fired.trigger_id, pattern = %fired.pattern, "Watch trigger fired");
+            match fired.action {
+                WatchAction::Notify => {
+                    let _ = notifiers.events.send(SessionEvent {
+                        session_id: session_id.to_string(),
+                        kind: SessionEventKind::OutputMatched {
+                            trigger_id: fired.trigger_id,
+                            pattern: fired.pattern,
+                            line: text.to_string(),
+                        },
+                    });
+                }
+                action => {
+                    let _ = notifiers.tasks.send(SessionTask::WatchAction {
+                        session_id: session_id.to_string(),
+                        action,
+                    });
+                }
+            }
+        }
+        line.drain(..=end);
+    }
+    if line.len() > MAX_WATCH_LINE {
+        line.clear();
+    }
+}
+
+/// Append to an output buffer, trimming it to the newest `MAX_BUFFER_SIZE` bytes
+fn append_capped(buffer: &mut Vec<u8>, data: &[u8]) {
+    buffer.extend_from_slice(data);
+    if buffer.len() > MAX_BUFFER_SIZE {
+        let drain_to = buffer.len() - MAX_BUFFER_SIZE;
+        buffer.drain(..drain_to);
     }
 }
 
@@ -0,0 +1,432 @@
+use chrono::{DateTime, Utc};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::tmux;
+
+/// Longest OSC sequence the tracker buffers; longer ones are not markers
+const MAX_OSC_LEN: usize = 4096;
+
+/// Longest command line echo kept between the end of the prompt and the
+/// start of the command's output
+const MAX_ECHO_LEN: usize = 4096;
+
+/// bash: sourced with `--rcfile` instead of `~/.bashrc`, which it sources
+/// first. PS0 sets a flag so empty command lines don't report an exit.
+/// Exported variables are saved at every prompt for `fork_session`, except
+/// the ones that belong to this terminal.
+const BASH_SCRIPT: &str = r#"
+if [ -f ~/.bashrc ]; then . ~/.bashrc; fi
+
+if [ -n "${MCP_TERMINAL_FORK_ENV-}" ]; then
+    __mcp_terminal_env_file=$MCP_TERMINAL_ENV_FILE
+    . "$MCP_TERMINAL_FORK_ENV" 2>/dev/null
+    MCP_TERMINAL_ENV_FILE=$__mcp_terminal_env_file
+    unset MCP_TERMINAL_FORK_ENV __mcp_terminal_env_file
+fi
+
+__mcp_terminal_precmd() {
+    local status=$?
+    if [ -n "${__mcp_terminal_ran+x}" ]; then
+        printf '\e]133;D;%s\a' "$status"
+        unset __mcp_terminal_ran
+    fi
+    printf '\e]7;file://%s%s\a' "$HOSTNAME" "$PWD"
+    if [ -n "${MCP_TERMINAL_ENV_FILE-}" ]; then
+        (unset TMUX TMUX_PANE SHLVL OLDPWD PWD ${!MCP_TERMINAL_*}; export -p) >| "$MCP_TERMINAL_ENV_FILE"
+    fi
+    return $status
+}
+
+__mcp_terminal_prompt() {
+    # Prompt frameworks may rebuild PS1 on every prompt
+    case "$PS1" in
+        *'133;A'*) ;;
+        *) PS1='\[\e]133;A\a\]'"$PS1"'\[\e]133;B\a\]' ;;
+    esac
+    case "$PS0" in
+        *'133;C'*) ;;
+        *) PS0="$PS0"'${__mcp_terminal_ran:=}\e]133;C\a' ;;
+    esac
+}
+
+# Newlines, since the user's PROMPT_COMMAND may end with a semicolon
+PROMPT_COMMAND=$'__mcp_terminal_precmd\n'"${PROMPT_COMMAND-}"$'\n__mcp_terminal_prompt'
+"#;
+
+/// zsh: `.zshenv`, found through `ZDOTDIR`. Sources the user's own
+/// `.zshenv` and leaves `ZDOTDIR` pointing back here only until `.zshrc`.
+const ZSH_ENV_SCRIPT: &str = r#"
+__mcp_terminal_zdotdir=$ZDOTDIR
+if [[ -n ${MCP_TERMINAL_USER_ZDOTDIR-} ]]; then ZDOTDIR=$MCP_TERMINAL_USER_ZDOTDIR; else unset ZDOTDIR; fi
+[[ -f ${ZDOTDIR:-$HOME}/.zshenv ]] && source ${ZDOTDIR:-$HOME}/.zshenv
+if [[ -o interactive ]]; then
+    MCP_TERMINAL_USER_ZDOTDIR=${ZDOTDIR-}
+    ZDOTDIR=$__mcp_terminal_zdotdir
+fi
+unset __mcp_terminal_zdotdir
+"#;
+
+/// zsh: `.zshrc`. Restores `ZDOTDIR`, sources the user's `.zshrc` and adds
+/// the hooks.
+const ZSH_RC_SCRIPT: &str = r#"
+if [[ -n ${MCP_TERMINAL_USER_ZDOTDIR-} ]]; then ZDOTDIR=$MCP_TERMINAL_USER_ZDOTDIR; else unset ZDOTDIR; fi
+unset MCP_TERMINAL_USER_ZDOTDIR
+[[ -f ${ZDOTDIR:-$HOME}/.zshrc ]] && source ${ZDOTDIR:-$HOME}/.zshrc
+
+if [[ -n ${MCP_TERMINAL_FORK_ENV-} ]]; then
+    __mcp_terminal_env_file=$MCP_TERMINAL_ENV_FILE
+    source $MCP_TERMINAL_FORK_ENV 2>/dev/null
+    MCP_TERMINAL_ENV_FILE=$__mcp_terminal_env_file
+    unset MCP_TERMINAL_FORK_ENV __mcp_terminal_env_file
+fi
+
+__mcp_terminal_precmd() {
+    local exit_status=$?
+    if [[ -n ${__mcp_terminal_ran-} ]]; then
+        printf '\e]133;D;%s\a' $exit_status
+        unset __mcp_terminal_ran
+    fi
+    printf '\e]7;file://%s%s\a' $HOST $PWD
+    # Without the variables that belong to this terminal
+    [[ -n ${MCP_TERMINAL_ENV_FILE-} ]] &&
+        (unset TMUX TMUX_PANE SHLVL OLDPWD PWD ${(k)parameters[(I)MCP_TERMINAL_*]}; export -p) >| $MCP_TERMINAL_ENV_FILE
+    # Prompt frameworks may rebuild PS1 on every prompt
+    [[ $PS1 == *'133;A'* ]] || PS1=$'%{\e]133;A\a%}'$PS1$'%{\e]133;B\a%}'
+    return $exit_status
+}
+
+__mcp_terminal_preexec() {
+    __mcp_terminal_ran=1
+    printf '\e]133;C\a'
+}
+
+# First, so it sees the command's exit status
+precmd_functions=(__mcp_terminal_precmd $precmd_functions)
+preexec_functions+=(__mcp_terminal_preexec)
+"#;
+
+/// fish: sourced with `--init-command`, after the user's config
+const FISH_SCRIPT: &str = r#"
+function __mcp_terminal_postexec --on-event fish_postexec
+    set -g __mcp_terminal_status $status
+    set -g __mcp_terminal_ran 1
+end
+
+function __mcp_terminal_prompt --on-event fish_prompt
+    if set -q __mcp_terminal_ran
+        printf '\e]133;D;%s\a' $__mcp_terminal_status
+        set -e __mcp_terminal_ran
+    end
+    printf '\e]7;file://%s%s\a' $hostname $PWD
+    printf '\e]133;A\a'
+end
+
+function __mcp_terminal_preexec --on-event fish_preexec
+    printf '\e]133;C\a'
+end
+
+functions -c fish_prompt __mcp_terminal_user_prompt
+function fish_prompt
+    __mcp_terminal_user_prompt
+    printf '\e]133;B\a'
+end
+"#;
+
+/// Whether shells spawned for sessions get the integration snippet
+#[derive(Clone, Copy, Debug)]
+pub struct ShellIntegration {
+    enabled: bool,
+}
+
+/// Extra arguments and environment that make a shell load the integration
+#[derive(Default)]
+pub struct Injection {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+impl ShellIntegration {
+    /// Read `MCP_TERMINAL_SHELL_INTEGRATION`: `on` (default) or `off`
+    pub fn from_env() -> Result<Self, String> {
+        let enabled = match std::env::var("MCP_TERMINAL_SHELL_INTEGRATION").as_deref() {
+            Err(_) | Ok("") | Ok("on") | Ok("1") | Ok("true") => true,
+            Ok("off") | Ok("0") | Ok("false") => false,
+            Ok(other) => return Err(format!("Invalid MCP_TERMINAL_SHELL_INTEGRATION: {}", other)),
+        };
+        Ok(Self { enabled })
+    }
+
+    /// How to start `shell` for a session with the integration loaded,
+    /// taking on the exported variables saved in `fork_env`. Shells other
+    /// than bash, zsh and fish are started as they are.
+    pub fn inject(&self, shell: &str, session_id: &str, fork_env: Option<&Path>) -> Result<Injection, String> {
+        if !self.enabled {
+            return Ok(Injection::default());
+        }
+        let name = Path::new(shell).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let dir = || -> Result<PathBuf, String> { Ok(tmux::state_dir()?.join("shell-integration")) };
+        // Where bash and zsh save their exported variables
+        let env_vars = || -> Result<Vec<(String, String)>, String> {
+            let mut env = vec![(
+                "MCP_TERMINAL_ENV_FILE".to_string(),
+                create_env_file(session_id)?.to_string_lossy().into_owned(),
+            )];
+            if let Some(fork_env) = fork_env {
+                env.push(("MCP_TERMINAL_FORK_ENV".to_string(), fork_env.to_string_lossy().into_owned()));
+            }
+            Ok(env)
+        };
+        match name {
+            "bash" => {
+                let path = write_script(&dir()?, "bash/rc.bash", BASH_SCRIPT)?;
+                Ok(Injection {
+                    args: vec!["--rcfile".to_string(), path.to_string_lossy().into_owned()],
+                    env: env_vars()?,
+                })
+            }
+            "zsh" => {
+                let zdotdir = dir()?.join("zsh");
+                write_script(&zdotdir, ".zshenv", ZSH_ENV_SCRIPT)?;
+                write_script(&zdotdir, ".zshrc", ZSH_RC_SCRIPT)?;
+                let mut env = vec![("ZDOTDIR".to_string(), zdotdir.to_string_lossy().into_owned())];
+                if let Ok(user) = std::env::var("ZDOTDIR") {
+                    env.push(("MCP_TERMINAL_USER_ZDOTDIR".to_string(), user));
+                }
+                env.extend(env_vars()?);
+                Ok(Injection { args: Vec::new(), env })
+            }
+            "fish" => {
+                let path = write_script(&dir()?, "fish/integration.fish", FISH_SCRIPT)?;
+                let quoted = path.to_string_lossy().replace('\\', "\\\\").replace('\'', "\\'");
+                Ok(Injection {
+                    args: vec!["--init-command".to_string(), format!("source '{}'", quoted)],
+                    env: Vec::new(),
+                })
+            }
+            _ => Ok(Injection::default()),
+        }
+    }
+}
+
+/// File where a session's shell saves its exported variables, as commands
+/// the same shell can source
+pub fn env_file(session_id: &str) -> Result<PathBuf, String> {
+    Ok(tmux::state_dir()?
+        .join("shell-integration/env")
+        .join(format!("{}.sh", session_id)))
+}
+
+/// Create a session's empty env file, readable only by us since the
+/// environment may hold secrets. The shell truncates it without changing
+/// the mode.
+fn create_env_file(session_id: &str) -> Result<PathBuf, String> {
+    let path = env_file(session_id)?;
+    let fail = |e: std::io::Error| format!("Failed to create {}: {}", path.display(), e);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(fail)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .mode(0o600)
+        .open(&path)
+        .map_err(fail)?;
+    Ok(path)
+}
+
+/// Remove a closed session's env file
+pub fn remove_env_file(session_id: &str) {
+    if let Ok(path) = env_file(session_id) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Write a script unless it is already up to date
+fn write_script(dir: &Path, name: &str, contents: &str) -> Result<PathBuf, String> {
+    let path = dir.join(name);
+    if std::fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+        return Ok(path);
+    }
+    let fail = |e: std::io::Error| format!("Failed to write shell integration {}: {}", path.display(), e);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(fail)?;
+    }
+    std::fs::write(&path, contents).map_err(fail)?;
+    Ok(path)
+}
+
+/// What shell integration has reported about a session
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct ShellState {
+    /// Working directory reported by the shell (OSC 7)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Exit code of the last finished command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_exit_code: Option<i32>,
+    /// Whether a command is running, rather than the shell waiting at its prompt
+    pub command_running: bool,
+    /// When the running or last command started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_started_at: Option<DateTime<Utc>>,
+    /// Number of commands that finished
+    pub commands_finished: u64,
+}
+
+/// Parser state between output chunks
+#[derive(Default)]
+enum Scan {
+    #[default]
+    Ground,
+    Escape,
+    Osc(Vec<u8>),
+    /// ESC inside an OSC, possibly starting its ST terminator
+    OscEscape(Vec<u8>),
+}
+
+/// A command boundary reported by the shell
+pub enum ShellMark {
+    /// The command's output starts at `offset`. `echo` is what the terminal
+    /// showed after the prompt, i.e. the command line, with escape
+    /// sequences.
+    CommandStarted { echo: String, offset: u64 },
+    /// The command's output ended at `offset`
+    CommandFinished { exit_code: Option<i32>, offset: u64 },
+}
+
+/// Follows OSC 133 (prompt, command, output, exit) and OSC 7 (cwd) markers
+/// in a session's output. Sequences may be split across chunks.
+#[derive(Default)]
+pub struct ShellTracker {
+    scan: Scan,
+    /// Set once the first marker is seen
+    state: Option<ShellState>,
+    /// Bytes of output seen so far
+    offset: u64,
+    /// Offset of the escape sequence being parsed
+    sequence_start: u64,
+    /// Output since the end of the prompt, while the command line is being
+    /// entered
+    echo: Option<Vec<u8>>,
+}
+
+impl ShellTracker {
+    /// What the shell has reported so far; `None` without shell integration
+    pub fn state(&self) -> Option<&ShellState> {
+        self.state.as_ref()
+    }
+
+    /// Bytes of output seen so far
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Follow a chunk of output. Returns the command boundaries in it.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<ShellMark> {
+        let mut marks = Vec::new();
+        for &b in data {
+            self.offset += 1;
+            if let Some(echo) = self.echo.as_mut().filter(|echo| echo.len() < MAX_ECHO_LEN) {
+                echo.push(b);
+            }
+            self.scan = match std::mem::take(&mut self.scan) {
+                Scan::Ground if b == 0x1b => {
+                    self.sequence_start = self.offset - 1;
+                    Scan::Escape
+                }
+                Scan::Ground => Scan::Ground,
+                Scan::Escape if b == b']' => Scan::Osc(Vec::new()),
+                Scan::Escape if b == 0x1b => Scan::Escape,
+                Scan::Escape => Scan::Ground,
+                Scan::Osc(osc) if b == 0x07 => {
+                    marks.extend(self.marker(&osc));
+                    Scan::Ground
+                }
+                Scan::Osc(osc) if b == 0x1b => Scan::OscEscape(osc),
+                Scan::Osc(mut osc) => {
+                    osc.push(b);
+                    if osc.len() > MAX_OSC_LEN {
+                        Scan::Ground
+                    } else {
+                        Scan::Osc(osc)
+                    }
+                }
+                Scan::OscEscape(osc) => {
+                    marks.extend(self.marker(&osc));
+                    if b == b'\\' {
+                        Scan::Ground
+                    } else if b == b']' {
+                        // ESC ] without ST: a new OSC right away
+                        self.sequence_start = self.offset - 2;
+                        Scan::Osc(Vec::new())
+                    } else {
+                        Scan::Ground
+                    }
+                }
+            };
+        }
+        marks
+    }
+
+    fn marker(&mut self, osc: &[u8]) -> Option<ShellMark> {
+        let osc = std::str::from_utf8(osc).ok()?;
+        if let Some(marker) = osc.strip_prefix("133;") {
+            let state = self.state.get_or_insert_with(ShellState::default);
+            let mut params = marker.split(';');
+            match params.next() {
+                Some("A") => {
+                    state.command_running = false;
+                    self.echo = None;
+                }
+                Some("B") => {
+                    state.command_running = false;
+                    self.echo = Some(Vec::new());
+                }
+                Some("C") => {
+                    state.command_running = true;
+                    state.command_started_at = Some(Utc::now());
+                    let echo = self.echo.take().unwrap_or_default();
+                    return Some(ShellMark::CommandStarted {
+                        echo: String::from_utf8_lossy(&echo).into_owned(),
+                        offset: self.offset,
+                    });
+                }
+                Some("D") => {
+                    state.command_running = false;
+                    state.last_exit_code = params.next().and_then(|code| code.parse().ok());
+                    state.commands_finished += 1;
+                    return Some(ShellMark::CommandFinished {
+                        exit_code: state.last_exit_code,
+                        offset: self.sequence_start,
+                    });
+                }
+                _ => {}
+            }
+        } else if let Some(url) = osc.strip_prefix("7;") {
+            if let Some(cwd) = cwd_from_url(url) {
+                self.state.get_or_insert_with(ShellState::default).cwd = Some(cwd);
+            }
+        }
+        None
+    }
+}
+
+/// Path of a `file://host/path` URL, percent-decoded
+fn cwd_from_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut raw = path.bytes();
+    while let Some(b) = raw.next() {
+        if b == b'%' {
+            let hex = [raw.next()?, raw.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
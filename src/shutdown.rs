@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::signal::{self, unix::SignalKind};
+
+/// Default time processes get to exit after SIGHUP/SIGTERM before SIGKILL
+const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+
+/// How the server tears down sessions and running commands when it stops
+#[derive(Clone, Debug)]
+pub struct ShutdownPolicy {
+    /// Time to wait for processes to exit before force-killing them
+    pub grace: Duration,
+    /// Directory to write each session's unread output to, if set
+    pub transcript_dir: Option<PathBuf>,
+}
+
+impl ShutdownPolicy {
+    /// Read the policy from `MCP_TERMINAL_SHUTDOWN_GRACE_SECS` (default 5)
+    /// and `MCP_TERMINAL_SHUTDOWN_TRANSCRIPT_DIR`
+    pub fn from_env() -> Result<Self, String> {
+        let grace = match std::env::var("MCP_TERMINAL_SHUTDOWN_GRACE_SECS") {
+            Ok(value) if !value.is_empty() => Duration::from_secs(
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid MCP_TERMINAL_SHUTDOWN_GRACE_SECS: {}", value))?,
+            ),
+            _ => DEFAULT_GRACE,
+        };
+
+        let transcript_dir = match std::env::var("MCP_TERMINAL_SHUTDOWN_TRANSCRIPT_DIR") {
+            Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+            _ => None,
+        };
+
+        Ok(Self {
+            grace,
+            transcript_dir,
+        })
+    }
+}
+
+/// Wait for SIGINT or SIGTERM. Returns the name of the signal received.
+pub async fn wait_for_signal() -> &'static str {
+    let mut sigterm = match signal::unix::signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGTERM: {}", e);
+            let _ = signal::ctrl_c().await;
+            return "SIGINT";
+        }
+    };
+    tokio::select! {
+        _ = signal::ctrl_c() => "SIGINT",
+        _ = sigterm.recv() => "SIGTERM",
+    }
+}
@@ -0,0 +1,163 @@
+use axum::extract::connect_info::Connected;
+use axum::serve::{IncomingStream, Listener};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Time a client gets to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections that finished the handshake but were not yet accepted
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Load the TLS configuration of the HTTP transport from
+/// `MCP_TERMINAL_TLS_CERT` and `MCP_TERMINAL_TLS_KEY` (PEM files). When
+/// `MCP_TERMINAL_TLS_CLIENT_CA` is also set, client certificates signed by
+/// that CA are verified and can be used to authenticate. Returns `None`
+/// when TLS is not configured.
+pub fn config_from_env() -> Result<Option<Arc<ServerConfig>>, String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let (cert_path, key_path) = match (var("MCP_TERMINAL_TLS_CERT"), var("MCP_TERMINAL_TLS_KEY")) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => return Err("MCP_TERMINAL_TLS_CERT and MCP_TERMINAL_TLS_KEY must be set together".to_string()),
+    };
+
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read TLS certificate {}: {}", cert_path, e))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .map_err(|e| format!("Failed to read TLS key {}: {}", key_path, e))?;
+
+    let builder = match var("MCP_TERMINAL_TLS_CLIENT_CA") {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(&ca_path)
+                .map_err(|e| format!("Failed to read client CA {}: {}", ca_path, e))?
+            {
+                let cert = cert.map_err(|e| format!("Failed to read client CA {}: {}", ca_path, e))?;
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid client CA {}: {}", ca_path, e))?;
+            }
+            // Clients without a certificate may still authenticate with a token
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| format!("Invalid client CA {}: {}", ca_path, e))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Some(Arc::new(config)))
+}
+
+/// Remote end of a TLS connection
+#[derive(Clone, Debug)]
+pub struct TlsPeer {
+    pub addr: SocketAddr,
+    /// Subject common name of the verified client certificate, if one was presented
+    pub cert_subject: Option<String>,
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for TlsPeer {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        stream.remote_addr().clone()
+    }
+}
+
+/// TCP listener that completes TLS handshakes in the background, so a slow
+/// client cannot hold up everyone else
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<(TlsStream<TcpStream>, TlsPeer)>,
+}
+
+impl TlsListener {
+    pub async fn bind(addr: SocketAddr, config: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            tracing::debug!(%addr, "TLS handshake failed: {}", e);
+                            return;
+                        }
+                        Err(_) => {
+                            tracing::debug!(%addr, "TLS handshake timed out");
+                            return;
+                        }
+                    };
+                    let cert_subject = stream
+                        .get_ref()
+                        .1
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .and_then(|cert| subject_common_name(cert));
+                    let _ = tx.send((stream, TlsPeer { addr, cert_subject })).await;
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            accepted,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = TlsPeer;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(conn) => conn,
+            // The accept task only exits once we are dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(TlsPeer {
+            addr: self.local_addr,
+            cert_subject: None,
+        })
+    }
+}
+
+/// Subject common name of a DER certificate
+fn subject_common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(str::to_string)
+}
@@ -0,0 +1,393 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Dedicated tmux server socket, so we never touch the user's own tmux sessions
+const SOCKET: &str = "mcp-terminal";
+
+/// Prefix of tmux session names owned by this server; the rest is the session ID
+const NAME_PREFIX: &str = "mcp-";
+
+/// Rotate a session's output log once the reader has consumed this much
+const MAX_LOG_SIZE: u64 = 8 * 1024 * 1024;
+
+/// How long a rotated log stays quiet before its writer counts as gone
+const ROTATE_DRAIN: Duration = Duration::from_millis(500);
+
+/// How often the log reader checks whether the pane is still alive while idle
+const ALIVE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Metadata stored as tmux user options, so it survives server restarts
+pub struct TmuxMeta {
+    pub session_id: String,
+    pub name: Option<String>,
+    pub project: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub description: Option<String>,
+    pub cwd: String,
+    pub shell: String,
+    pub created_at: DateTime<Utc>,
+    /// Client that created the session
+    pub owner: Option<String>,
+    /// Clients the owner shared the session with
+    pub shared_with: Vec<String>,
+}
+
+/// A shell hosted in a detached tmux session.
+///
+/// Output is captured with `pipe-pane` into a log file that is tailed by
+/// [`LogReader`]; input is delivered with `send-keys`.
+pub struct TmuxSession {
+    name: String,
+    log_path: PathBuf,
+}
+
+impl TmuxSession {
+    /// Start a new detached tmux session running `meta.shell` with `shell_args`
+    pub fn spawn(meta: &TmuxMeta, shell_args: &[String], env: &[(&str, &str)]) -> Result<Self, String> {
+        let session = Self::for_id(&meta.session_id)?;
+        let _ = std::fs::remove_file(&session.log_path);
+
+        let pipe_cmd = pipe_command(&session.log_path);
+        let created_at = meta.created_at.to_rfc3339();
+
+        let mut args: Vec<&str> = vec!["new-session", "-d", "-s", &session.name, "-x", "200", "-y", "24"];
+        args.extend(["-c", &meta.cwd]);
+        let env_args: Vec<String> = env.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        for var in &env_args {
+            args.extend(["-e", var]);
+        }
+        args.push(&meta.shell);
+        args.extend(shell_args.iter().map(String::as_str));
+        // Chain the setup into the same tmux invocation, so the pipe is in
+        // place before tmux reads any output from the shell
+        args.extend([";", "set-option", "-w", "-t", &session.name, "remain-on-exit", "on"]);
+        args.extend([";", "pipe-pane", "-o", "-t", &session.name, &pipe_cmd]);
+        args.extend([";", "set-option", "-t", &session.name, "@mcp_cwd", &meta.cwd]);
+        args.extend([";", "set-option", "-t", &session.name, "@mcp_shell", &meta.shell]);
+        args.extend([";", "set-option", "-t", &session.name, "@mcp_created", &created_at]);
+        if let Some(project) = &meta.project {
+            args.extend([";", "set-option", "-t", &session.name, "@mcp_project", project]);
+        }
+        if let Some(owner) = &meta.owner {
+            args.extend([";", "set-option", "-t", &session.name, "@mcp_owner", owner]);
+        }
+        if let Some(name) = &meta.name {
+            args.extend([";", "set-option", "-t", &session.name, "@mcp_name", name]);
+        }
+        // JSON-encoded, so tabs and newlines can't break `list`
+        let labels = serde_json::to_string(&meta.labels).unwrap_or_default();
+        let description = meta.description.as_ref().map(|d| serde_json::Value::from(d.as_str()).to_string());
+        if !meta.labels.is_empty() {
+            args.extend([";", "set-option", "-t", &session.name, "@mcp_labels", &labels]);
+        }
+        if let Some(description) = &description {
+            args.extend([";", "set-option", "-t", &session.name, "@mcp_description", description]);
+        }
+
+        tmux(&args).map_err(|e| format!("Failed to start tmux session: {}", e))?;
+        Ok(session)
+    }
+
+    /// Find sessions left running by a previous server process
+    pub fn list() -> Vec<TmuxMeta> {
+        let format = "#{session_name}\t#{@mcp_project}\t#{@mcp_cwd}\t#{@mcp_shell}\t#{@mcp_created}\t#{@mcp_owner}\t#{@mcp_shared}\t#{@mcp_name}\t#{@mcp_labels}\t#{@mcp_description}";
+        let Ok(out) = tmux(&["list-sessions", "-F", format]) else {
+            // No tmux server running means no sessions to restore
+            return Vec::new();
+        };
+
+        out.lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let [tmux_name, project, cwd, shell, created, owner, shared, name, labels, description] = fields[..] else {
+                    return None;
+                };
+                Some(TmuxMeta {
+                    session_id: tmux_name.strip_prefix(NAME_PREFIX)?.to_string(),
+                    name: (!name.is_empty()).then(|| name.to_string()),
+                    project: (!project.is_empty()).then(|| project.to_string()),
+                    labels: serde_json::from_str(labels).unwrap_or_default(),
+                    description: serde_json::from_str(description).ok(),
+                    cwd: cwd.to_string(),
+                    shell: shell.to_string(),
+                    created_at: DateTime::parse_from_rfc3339(created)
+                        .map(|t| t.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                    owner: (!owner.is_empty()).then(|| owner.to_string()),
+                    shared_with: shared
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// Handle to an existing session by ID
+    pub fn for_id(session_id: &str) -> Result<Self, String> {
+        Ok(Self {
+            name: format!("{}{}", NAME_PREFIX, session_id),
+            log_path: state_dir()?.join(format!("{}.log", session_id)),
+        })
+    }
+
+    /// PID of the shell running in the session's pane
+    pub fn pid(&self) -> Option<u32> {
+        self.display("#{pane_pid}").ok()?.parse().ok()
+    }
+
+    /// Exit code of the shell, once it has exited
+    pub fn exit_code(&self) -> Option<u32> {
+        let status = self.display("#{pane_dead} #{pane_dead_status}").ok()?;
+        match status.split_once(' ') {
+            Some(("1", code)) => code.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Reader that tails the session's output log
+    pub fn reader(&self, backlog: u64) -> Result<LogReader, String> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.log_path)
+            .map_err(|e| format!("Failed to open tmux log: {}", e))?;
+        // Replay recent output when reattaching to an existing session
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        file.seek(SeekFrom::Start(len.saturating_sub(backlog)))
+            .map_err(|e| format!("Failed to seek tmux log: {}", e))?;
+
+        Ok(LogReader {
+            file,
+            name: self.name.clone(),
+            log_path: self.log_path.clone(),
+            draining_since: None,
+            last_alive_check: Instant::now(),
+        })
+    }
+
+    /// Writer that types input into the session's pane
+    pub fn writer(&self) -> TmuxWriter {
+        TmuxWriter {
+            name: self.name.clone(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Record who the session is shared with, so it survives restarts
+    pub fn set_shared_with(&self, clients: &[String]) -> Result<(), String> {
+        let shared = clients.join(",");
+        tmux(&["set-option", "-t", &self.target(), "@mcp_shared", &shared])
+            .map(|_| ())
+            .map_err(|e| format!("Failed to update tmux session: {}", e))
+    }
+
+    /// Update the labels and description kept for a server restart
+    pub fn set_metadata(&self, labels: &BTreeMap<String, String>, description: Option<&str>) -> Result<(), String> {
+        let labels = serde_json::to_string(labels).unwrap_or_default();
+        let description = description.map(|d| serde_json::Value::from(d).to_string()).unwrap_or_default();
+        tmux(&["set-option", "-t", &self.target(), "@mcp_labels", &labels])
+            .and_then(|_| tmux(&["set-option", "-t", &self.target(), "@mcp_description", &description]))
+            .map(|_| ())
+            .map_err(|e| format!("Failed to update tmux session: {}", e))
+    }
+
+    /// Kill the tmux session and remove its output log
+    pub fn kill(&self) {
+        if let Err(e) = tmux(&["kill-session", "-t", &self.target()]) {
+            tracing::warn!(session = %self.name, "Failed to kill tmux session: {}", e);
+        }
+        let _ = std::fs::remove_file(&self.log_path);
+        let _ = std::fs::remove_file(rotated_path(&self.log_path));
+    }
+
+    fn target(&self) -> String {
+        target(&self.name)
+    }
+
+    fn display(&self, format: &str) -> Result<String, String> {
+        tmux(&["display-message", "-p", "-t", &self.target(), format])
+    }
+}
+
+/// Target the (only) pane of session `name`. "=" requests an exact match
+/// rather than a prefix match.
+fn target(name: &str) -> String {
+    format!("={}:", name)
+}
+
+/// Whether the pane of tmux session `name` is still running its shell.
+/// Transient tmux failures count as alive; a missing session does not.
+fn pane_alive(name: &str) -> bool {
+    match tmux(&["display-message", "-p", "-t", &target(name), "#{pane_dead}"]) {
+        Ok(dead) => dead != "1",
+        Err(e) => !(e.contains("can't find") || e.contains("no server running")),
+    }
+}
+
+/// Command that appends a pane's output to `log_path`
+fn pipe_command(log_path: &Path) -> String {
+    format!("cat >> {}", shell_quote(&log_path.to_string_lossy()))
+}
+
+/// Where a log is moved while the reader drains it after rotation
+fn rotated_path(log_path: &Path) -> PathBuf {
+    log_path.with_extension("log.old")
+}
+
+/// Blocking reader over a tmux session's output log. Returns EOF once the
+/// pane's shell has exited and all output has been consumed.
+pub struct LogReader {
+    file: File,
+    name: String,
+    log_path: PathBuf,
+    /// While the rotated log is still being read, when it last had output
+    draining_since: Option<Instant>,
+    last_alive_check: Instant,
+}
+
+impl LogReader {
+    /// Keep the log from growing without bound: move it aside and point
+    /// pipe-pane at a fresh one. The old pipe keeps appending to the moved
+    /// file until tmux closes it, so it is read to the end before the
+    /// reader switches over.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = rotated_path(&self.log_path);
+        std::fs::rename(&self.log_path, &rotated)?;
+        // Without -o this replaces the open pipe, so no output is missed
+        let pipe_cmd = pipe_command(&self.log_path);
+        if let Err(e) = tmux(&["pipe-pane", "-t", &target(&self.name), &pipe_cmd]) {
+            tracing::warn!(session = %self.name, "Failed to rotate tmux log: {}", e);
+            return std::fs::rename(&rotated, &self.log_path);
+        }
+        self.draining_since = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Continue with the new log once the rotated one has gone quiet
+    fn finish_rotation(&mut self) -> std::io::Result<()> {
+        self.file = File::open(&self.log_path)?;
+        self.draining_since = None;
+        let _ = std::fs::remove_file(rotated_path(&self.log_path));
+        Ok(())
+    }
+}
+
+impl Read for LogReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 {
+                // The old pipe is still flushing
+                if self.draining_since.is_some() {
+                    self.draining_since = Some(Instant::now());
+                }
+                return Ok(n);
+            }
+
+            // Caught up
+            match self.draining_since {
+                Some(since) if since.elapsed() >= ROTATE_DRAIN => {
+                    self.finish_rotation()?;
+                    continue;
+                }
+                Some(_) => {}
+                None if self.file.stream_position()? > MAX_LOG_SIZE => self.rotate()?,
+                None => {}
+            }
+
+            if self.draining_since.is_none() && self.last_alive_check.elapsed() >= ALIVE_CHECK_INTERVAL {
+                self.last_alive_check = Instant::now();
+                if !pane_alive(&self.name) {
+                    // Drain anything written just before the shell exited
+                    return self.file.read(buf);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+/// Writer that delivers input to a tmux pane with `send-keys` on flush
+pub struct TmuxWriter {
+    name: String,
+    pending: Vec<u8>,
+}
+
+impl Write for TmuxWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let input = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+        let target = target(&self.name);
+
+        // Send text literally, but newlines as Enter so programs that read
+        // keys (readline, REPLs) see a real key press
+        for (i, line) in input.split('\n').enumerate() {
+            if i > 0 {
+                tmux(&["send-keys", "-t", &target, "Enter"]).map_err(std::io::Error::other)?;
+            }
+            if !line.is_empty() {
+                tmux(&["send-keys", "-t", &target, "-l", "--", line]).map_err(std::io::Error::other)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the tmux binary is available
+pub fn available() -> bool {
+    Command::new("tmux")
+        .arg("-V")
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+/// Run a command against our tmux server and return its trimmed stdout
+fn tmux(args: &[&str]) -> Result<String, String> {
+    let out = Command::new("tmux")
+        .arg("-L")
+        .arg(SOCKET)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run tmux: {}", e))?;
+
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+    }
+}
+
+/// Directory for session output logs and shell integration scripts:
+/// `$MCP_TERMINAL_STATE_DIR`, or `$XDG_STATE_HOME/mcp-terminal`, or
+/// `~/.local/state/mcp-terminal`
+pub fn state_dir() -> Result<PathBuf, String> {
+    let dir = if let Ok(dir) = std::env::var("MCP_TERMINAL_STATE_DIR") {
+        PathBuf::from(dir)
+    } else if let Ok(dir) = std::env::var("XDG_STATE_HOME") {
+        PathBuf::from(dir).join("mcp-terminal")
+    } else {
+        let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+        PathBuf::from(home).join(".local/state/mcp-terminal")
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create state directory {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Quote a string for use in a `sh -c` command line
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,499 @@
+use std::fmt::Write;
+
+use crate::redact::Redactor;
+use crate::session::SessionInfo;
+
+/// Format of an exported transcript
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    /// Self-contained HTML page with the terminal's colors
+    Html,
+}
+
+impl Format {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format {
+            None | Some("markdown") | Some("md") => Ok(Self::Markdown),
+            Some("html") => Ok(Self::Html),
+            Some(other) => Err(format!("Unknown transcript format {}; use markdown or html", other)),
+        }
+    }
+}
+
+/// Piece of terminal output: text, or an SGR sequence changing its style
+enum Piece {
+    Text(String),
+    Sgr(String),
+}
+
+/// Which part of a command block output currently goes to, as told by
+/// OSC 133 shell integration markers
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Prompt,
+    Command,
+    Output,
+}
+
+/// A command with its prompt, output and exit code. Output before the
+/// first prompt, or all output without shell integration, is a block
+/// without a command.
+#[derive(Default)]
+struct Block {
+    prompt: Vec<Piece>,
+    command: Vec<Piece>,
+    output: Vec<Piece>,
+    exit_code: Option<i32>,
+}
+
+impl Block {
+    fn is_empty(&self) -> bool {
+        self.prompt.is_empty() && self.command.is_empty() && self.output.is_empty()
+    }
+
+    fn part(&mut self, part: Part) -> &mut Vec<Piece> {
+        match part {
+            Part::Prompt => &mut self.prompt,
+            Part::Command => &mut self.command,
+            Part::Output => &mut self.output,
+        }
+    }
+}
+
+/// Split terminal output into command blocks at OSC 133 markers, keeping
+/// text and SGR sequences and dropping every other escape sequence.
+/// Returns the blocks and whether any marker was seen.
+fn split_blocks(text: &str) -> (Vec<Block>, bool) {
+    let mut blocks = vec![Block::default()];
+    let mut part = Part::Output;
+    let mut marked = false;
+    let mut chars = text.char_indices().peekable();
+    let mut run_start = 0;
+
+    let flush = |blocks: &mut Vec<Block>, part: Part, run: &str| {
+        if !run.is_empty() {
+            let block = blocks.last_mut().expect("blocks is never empty");
+            block.part(part).push(Piece::Text(run.to_string()));
+        }
+    };
+
+    while let Some((i, ch)) = chars.next() {
+        if ch != '\x1b' {
+            continue;
+        }
+        flush(&mut blocks, part, &text[run_start..i]);
+        match chars.next() {
+            Some((start, '[')) => {
+                // CSI: parameters up to a final byte in @..~
+                let mut end = text.len();
+                let mut final_byte = None;
+                for (j, c) in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        end = j;
+                        final_byte = Some(c);
+                        break;
+                    }
+                }
+                if final_byte == Some('m') {
+                    let block = blocks.last_mut().expect("blocks is never empty");
+                    block.part(part).push(Piece::Sgr(text[start + 1..end].to_string()));
+                }
+            }
+            Some((start, ']')) => {
+                // OSC: up to BEL or ST
+                let mut end = text.len();
+                while let Some((j, c)) = chars.next() {
+                    if c == '\x07' {
+                        end = j;
+                        break;
+                    }
+                    if c == '\x1b' {
+                        end = j;
+                        chars.next_if(|&(_, c)| c == '\\');
+                        break;
+                    }
+                }
+                if let Some(marker) = text[start + 1..end].strip_prefix("133;") {
+                    marked = true;
+                    let mut params = marker.split(';');
+                    match params.next() {
+                        Some("A") => {
+                            if !blocks.last().is_some_and(|b| b.is_empty()) {
+                                blocks.push(Block::default());
+                            }
+                            part = Part::Prompt;
+                        }
+                        Some("B") => part = Part::Command,
+                        Some("C") => part = Part::Output,
+                        Some("D") => {
+                            let block = blocks.last_mut().expect("blocks is never empty");
+                            block.exit_code = params.next().and_then(|code| code.parse().ok());
+                            part = Part::Output;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            // Character set designation and the like, e.g. ESC ( B after
+            // every reset from ncurses and `tput sgr0`: one more byte
+            Some((_, '(' | ')' | '*' | '+' | '#' | '%')) => {
+                chars.next();
+            }
+            // Two-character escape, e.g. ESC =
+            _ => {}
+        }
+        run_start = chars.peek().map_or(text.len(), |&(j, _)| j);
+    }
+    flush(&mut blocks, part, &text[run_start..]);
+    (blocks, marked)
+}
+
+/// Text style set by SGR sequences
+#[derive(Clone, Default, PartialEq, Eq)]
+struct Style {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl Style {
+    fn apply(&mut self, params: &str) {
+        let mut codes = params.split([';', ':']).map(|p| p.parse::<u16>().unwrap_or(0));
+        // An empty parameter list means reset
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(palette(code - 30)),
+                38 => self.fg = extended_color(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(palette(code - 40)),
+                48 => self.bg = extended_color(&mut codes),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(palette(code - 90 + 8)),
+                100..=107 => self.bg = Some(palette(code - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Inline CSS for the style, empty for the default style
+    fn css(&self) -> String {
+        let (mut fg, mut bg) = (self.fg.clone(), self.bg.clone());
+        if self.inverse {
+            (fg, bg) = (
+                Some(bg.unwrap_or_else(|| "var(--bg)".to_string())),
+                Some(fg.unwrap_or_else(|| "var(--fg)".to_string())),
+            );
+        }
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            let _ = write!(css, "color:{};", fg);
+        }
+        if let Some(bg) = bg {
+            let _ = write!(css, "background:{};", bg);
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.dim {
+            css.push_str("opacity:.7;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+}
+
+/// Color of a `38;5;n`/`38;2;r;g;b` (or `48;…`) SGR sequence
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<String> {
+    match codes.next()? {
+        5 => {
+            let n = codes.next()?;
+            Some(match n {
+                0..=15 => palette(n),
+                16..=231 => {
+                    let n = n - 16;
+                    let level = |v: u16| if v == 0 { 0 } else { 55 + v * 40 };
+                    format!("#{:02x}{:02x}{:02x}", level(n / 36), level(n / 6 % 6), level(n % 6))
+                }
+                232..=255 => {
+                    let v = 8 + (n - 232) * 10;
+                    format!("#{:02x}{:02x}{:02x}", v, v, v)
+                }
+                _ => return None,
+            })
+        }
+        2 => {
+            let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+            Some(format!("#{:02x}{:02x}{:02x}", r.min(255), g.min(255), b.min(255)))
+        }
+        _ => None,
+    }
+}
+
+/// The 16 standard terminal colors
+fn palette(n: u16) -> String {
+    const COLORS: [&str; 16] = [
+        "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+        "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+    ];
+    COLORS[n as usize % 16].to_string()
+}
+
+/// Styled runs of one line
+type Line = Vec<(Style, String)>;
+
+/// Lay pieces out as lines, applying carriage returns (a redrawn line,
+/// such as a progress bar, keeps only its last version) and backspaces
+fn layout(pieces: &[Piece]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line: Line = Vec::new();
+    let mut style = Style::default();
+    let mut carriage_return = false;
+    for piece in pieces {
+        let text = match piece {
+            Piece::Sgr(params) => {
+                style.apply(params);
+                continue;
+            }
+            Piece::Text(text) => text,
+        };
+        for ch in text.chars() {
+            match ch {
+                '\n' => {
+                    lines.push(std::mem::take(&mut line));
+                    carriage_return = false;
+                }
+                '\r' => carriage_return = true,
+                '\x08' => {
+                    if let Some((_, run)) = line.last_mut() {
+                        run.pop();
+                    }
+                }
+                '\t' => push_char(&mut line, &style, ' '),
+                c if c.is_control() => {}
+                c => {
+                    if carriage_return {
+                        line.clear();
+                        carriage_return = false;
+                    }
+                    push_char(&mut line, &style, c);
+                }
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l: &Line| plain(l).trim().is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn push_char(line: &mut Line, style: &Style, c: char) {
+    match line.last_mut() {
+        Some((last, run)) if last == style => run.push(c),
+        _ => line.push((style.clone(), c.to_string())),
+    }
+}
+
+fn plain(line: &Line) -> String {
+    line.iter().map(|(_, run)| run.as_str()).collect()
+}
+
+fn plain_text(pieces: &[Piece]) -> String {
+    layout(pieces).iter().map(plain).collect::<Vec<_>>().join("\n")
+}
+
+/// Redact secrets from the text of a block part. Color changes can split a
+/// secret (e.g. in colored `env` output), so the text is matched without
+/// them; a part that holds a secret loses its colors.
+fn redact_part(pieces: &mut Vec<Piece>, redactor: &Redactor) {
+    let text: String = pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Text(text) => Some(text.as_str()),
+            Piece::Sgr(_) => None,
+        })
+        .collect();
+    let (redacted, redactions) = redactor.redact(&text);
+    if !redactions.is_empty() {
+        *pieces = vec![Piece::Text(redacted)];
+    }
+}
+
+/// Render a session's raw output as a transcript, with secrets redacted
+pub fn render(info: &SessionInfo, output: &str, format: Format, redactor: &Redactor) -> String {
+    let (mut blocks, marked) = split_blocks(output);
+    for block in &mut blocks {
+        redact_part(&mut block.prompt, redactor);
+        redact_part(&mut block.command, redactor);
+        redact_part(&mut block.output, redactor);
+    }
+    // Output after the last command ends with a bare prompt
+    let blocks: Vec<Block> = blocks
+        .into_iter()
+        .filter(|b| !plain_text(&b.command).trim().is_empty() || !layout(&b.output).is_empty())
+        .collect();
+    match format {
+        Format::Markdown => markdown(info, &blocks, marked),
+        Format::Html => html(info, &blocks, marked),
+    }
+}
+
+fn markdown(info: &SessionInfo, blocks: &[Block], marked: bool) -> String {
+    let mut out = format!("# Terminal session {}\n\n", info.session_id);
+    if let Some(project) = &info.project {
+        let _ = writeln!(out, "- Project: {}", project);
+    }
+    let _ = writeln!(out, "- Working directory: `{}`", info.cwd);
+    let _ = writeln!(out, "- Started: {}", info.created_at.to_rfc3339());
+    if !marked {
+        out.push_str("\nShell integration was not available, so the output is not split into commands.\n");
+    }
+
+    for block in blocks {
+        let command = plain_text(&block.command);
+        let command = command.trim();
+        let output = layout(&block.output).iter().map(plain).collect::<Vec<_>>().join("\n");
+        out.push('\n');
+        if !command.is_empty() {
+            let heading = command.lines().next().unwrap_or_default();
+            let _ = writeln!(out, "## {}\n", code_span(heading));
+            let prompt = plain_text(&block.prompt);
+            let fence = fence_for(command);
+            let _ = writeln!(out, "{}console\n{}{}\n{}\n", fence, prompt.trim_start(), command, fence);
+        }
+        if !output.is_empty() {
+            let fence = fence_for(&output);
+            let _ = writeln!(out, "{}text\n{}\n{}\n", fence, output, fence);
+        }
+        if let Some(code) = block.exit_code {
+            let _ = writeln!(out, "Exit code: {}\n", code);
+        }
+    }
+    out
+}
+
+/// A code fence longer than any run of backticks in `text`
+fn fence_for(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Inline code that survives backticks in `text`
+fn code_span(text: &str) -> String {
+    let ticks = "`".repeat(fence_for(text).len() - 2);
+    format!("{} {} {}", ticks, text, ticks)
+}
+
+const HTML_STYLE: &str = "\
+:root{--fg:#e5e5e5;--bg:#1e1e1e}\
+body{background:var(--bg);color:var(--fg);font-family:ui-monospace,Menlo,Consolas,monospace;margin:2em}\
+header{color:#999;margin-bottom:1.5em}\
+section{border-left:3px solid #555;margin:1em 0;padding-left:1em}\
+section.ok{border-color:#0dbc79}section.fail{border-color:#cd3131}\
+pre{margin:.3em 0;white-space:pre-wrap;word-break:break-all}\
+.prompt{color:#999}.command{font-weight:bold}.exit{color:#999;font-size:.9em}";
+
+fn html(info: &SessionInfo, blocks: &[Block], marked: bool) -> String {
+    let title = format!("Terminal session {}", info.session_id);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<header>\n<h1>{}</h1>\n",
+        escape(&title),
+        HTML_STYLE,
+        escape(&title)
+    );
+    if let Some(project) = &info.project {
+        let _ = writeln!(out, "<div>Project: {}</div>", escape(project));
+    }
+    let _ = writeln!(out, "<div>Working directory: {}</div>", escape(&info.cwd));
+    let _ = writeln!(out, "<div>Started: {}</div>", escape(&info.created_at.to_rfc3339()));
+    if !marked {
+        out.push_str("<div>Shell integration was not available, so the output is not split into commands.</div>\n");
+    }
+    out.push_str("</header>\n");
+
+    for block in blocks {
+        let class = match block.exit_code {
+            Some(0) => "ok",
+            Some(_) => "fail",
+            None => "",
+        };
+        let _ = writeln!(out, "<section class=\"{}\">", class);
+        let command = plain_text(&block.command);
+        if !command.trim().is_empty() {
+            let _ = writeln!(
+                out,
+                "<pre><span class=\"prompt\">{}</span><span class=\"command\">{}</span></pre>",
+                escape(plain_text(&block.prompt).trim_start()),
+                escape(command.trim())
+            );
+        }
+        let output = layout(&block.output);
+        if !output.is_empty() {
+            out.push_str("<pre class=\"output\">");
+            for (i, line) in output.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                for (style, run) in line {
+                    let css = style.css();
+                    if css.is_empty() {
+                        out.push_str(&escape(run));
+                    } else {
+                        let _ = write!(out, "<span style=\"{}\">{}</span>", css, escape(run));
+                    }
+                }
+            }
+            out.push_str("</pre>\n");
+        }
+        if let Some(code) = block.exit_code {
+            let _ = writeln!(out, "<div class=\"exit\">exit code {}</div>", code);
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,404 @@
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use rmcp::{
+    model::{ClientJsonRpcMessage, GetExtensions, ServerJsonRpcMessage},
+    service::{RxJsonRpcMessage, TxJsonRpcMessage},
+    transport::{
+        streamable_http_server::session::local::LocalSessionManager, StreamableHttpServerConfig,
+        StreamableHttpService,
+    },
+    RoleServer, ServiceExt,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+use tokio_rustls::rustls::ServerConfig;
+use uuid::Uuid;
+
+use crate::auth::{Authenticator, Client};
+use crate::server::TerminalServer;
+use crate::tls::{TlsListener, TlsPeer};
+
+/// Default address of the HTTP transport
+const DEFAULT_LISTEN: &str = "127.0.0.1:8787";
+
+/// Messages buffered per legacy SSE client before senders wait
+const SSE_CHANNEL_CAPACITY: usize = 64;
+
+/// How clients connect to the server
+#[derive(Clone, Debug)]
+pub enum Transport {
+    /// A single client over stdin/stdout
+    Stdio,
+    /// Any number of clients over MCP Streamable HTTP (`/mcp`) and the
+    /// legacy HTTP+SSE transport (`/sse` and `/message`)
+    Http(ListenAddr),
+}
+
+/// Where the HTTP transport listens
+#[derive(Clone, Debug)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{}", addr),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl std::str::FromStr for ListenAddr {
+    type Err = String;
+
+    /// Parse `host:port` or `unix:/path/to/socket`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(Self::Unix(PathBuf::from(path))),
+            Some(_) => Err("Missing socket path in unix: listen address".to_string()),
+            None => s
+                .parse()
+                .map(Self::Tcp)
+                .map_err(|_| format!("Invalid listen address: {}", s)),
+        }
+    }
+}
+
+impl Transport {
+    /// Read the transport from the `--transport` (`stdio` or `http`) and
+    /// `--listen` (`host:port` or `unix:/path`) command-line arguments,
+    /// falling back to `MCP_TERMINAL_TRANSPORT` and `MCP_TERMINAL_LISTEN`.
+    /// Defaults to stdio, and to listening on 127.0.0.1:8787 for HTTP.
+    pub fn from_args() -> Result<Self, String> {
+        let mut transport = std::env::var("MCP_TERMINAL_TRANSPORT").ok();
+        let mut listen = std::env::var("MCP_TERMINAL_LISTEN").ok();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let target = match flag.as_str() {
+                "--transport" => &mut transport,
+                "--listen" => &mut listen,
+                _ => return Err(format!("Unknown argument: {}", flag)),
+            };
+            let value = inline
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            *target = Some(value);
+        }
+
+        match transport.as_deref() {
+            None | Some("") | Some("stdio") => Ok(Self::Stdio),
+            Some("http") => {
+                let listen = listen.filter(|l| !l.is_empty());
+                Ok(Self::Http(listen.as_deref().unwrap_or(DEFAULT_LISTEN).parse()?))
+            }
+            Some(other) => Err(format!("Invalid transport: {}", other)),
+        }
+    }
+}
+
+/// Identifies the legacy SSE connection a request arrived on, like the
+/// `Mcp-Session-Id` header does for Streamable HTTP
+#[derive(Clone, Debug)]
+pub struct ConnectionId(pub String);
+
+/// Security settings of the HTTP transport
+#[derive(Clone, Default)]
+pub struct HttpSecurity {
+    /// Clients allowed to connect; anyone may connect when unset
+    pub auth: Option<Arc<Authenticator>>,
+    /// Serve HTTPS with this configuration
+    pub tls: Option<Arc<ServerConfig>>,
+}
+
+/// Serve `server` over HTTP until `shutdown` is cancelled. Every client gets
+/// its own MCP connection; all of them share the server's sessions.
+pub async fn serve_http(
+    server: TerminalServer,
+    listen: &ListenAddr,
+    security: HttpSecurity,
+    shutdown: CancellationToken,
+) -> Result<(), String> {
+    let factory = server.clone();
+    let streamable = StreamableHttpService::new(
+        move || Ok(factory.for_connection()),
+        Arc::new(LocalSessionManager::default()),
+        StreamableHttpServerConfig {
+            cancellation_token: shutdown.child_token(),
+            ..Default::default()
+        },
+    );
+
+    let legacy = SseState {
+        server,
+        clients: Arc::new(Mutex::new(HashMap::new())),
+        shutdown: shutdown.clone(),
+    };
+
+    let mut router = Router::new()
+        .nest_service("/mcp", streamable)
+        .route("/sse", get(sse_connect))
+        .route("/message", post(sse_message))
+        .with_state(legacy);
+    match &security.auth {
+        Some(auth) => {
+            router = router.layer(middleware::from_fn_with_state(Arc::clone(auth), authenticate));
+        }
+        None => tracing::warn!("HTTP transport has no authentication; anyone who can connect can run commands"),
+    }
+
+    let bind_error = |e: std::io::Error| format!("Failed to listen on {}: {}", listen, e);
+    let scheme = if security.tls.is_some() { "https" } else { "http" };
+    match listen {
+        ListenAddr::Tcp(addr) => tracing::info!("Listening on {}://{}", scheme, addr),
+        ListenAddr::Unix(_) => tracing::info!("Listening on {}", listen),
+    }
+    match (listen, security.tls) {
+        (ListenAddr::Tcp(addr), Some(tls)) => {
+            let listener = TlsListener::bind(*addr, tls).await.map_err(bind_error)?;
+            axum::serve(listener, router.into_make_service_with_connect_info::<TlsPeer>())
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await
+        }
+        (ListenAddr::Tcp(addr), None) => {
+            let listener = tokio::net::TcpListener::bind(addr).await.map_err(bind_error)?;
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await
+        }
+        (ListenAddr::Unix(_), Some(_)) => {
+            return Err("TLS is not supported on Unix sockets".to_string());
+        }
+        (ListenAddr::Unix(path), None) => {
+            // A socket left behind by a previous run would make bind fail
+            let _ = std::fs::remove_file(path);
+            let listener = tokio::net::UnixListener::bind(path).map_err(bind_error)?;
+            let served = axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await;
+            let _ = std::fs::remove_file(path);
+            served
+        }
+    }
+    .map_err(|e| format!("HTTP server failed: {}", e))
+}
+
+/// Identify the client by its bearer token or TLS client certificate and
+/// attach it to the request, or reject the request
+async fn authenticate(
+    State(auth): State<Arc<Authenticator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let client = match token {
+        // A wrong token is rejected even if the certificate would do
+        Some(token) => auth.by_token(token.trim()),
+        None => request
+            .extensions()
+            .get::<ConnectInfo<TlsPeer>>()
+            .and_then(|info| info.0.cert_subject.as_deref())
+            .and_then(|subject| auth.by_cert_subject(subject)),
+    };
+
+    match client {
+        Some(client) => {
+            request.extensions_mut().insert(client);
+            next.run(request).await
+        }
+        None => {
+            let peer = request.extensions().get::<ConnectInfo<TlsPeer>>().map(|info| info.0.addr);
+            tracing::warn!(path = %request.uri().path(), peer = ?peer, "Rejected unauthenticated request");
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "Unauthorized",
+            )
+                .into_response()
+        }
+    }
+}
+
+// -- Legacy HTTP+SSE transport (protocol version 2024-11-05) --
+
+/// Shared state of the legacy SSE endpoints
+#[derive(Clone)]
+struct SseState {
+    server: TerminalServer,
+    clients: Arc<Mutex<HashMap<String, SseClient>>>,
+    shutdown: CancellationToken,
+}
+
+/// A connected SSE client
+struct SseClient {
+    /// Authenticated client that opened the stream
+    client: Option<Arc<Client>>,
+    /// Inbound message channel of the connection
+    inbound: mpsc::Sender<ClientJsonRpcMessage>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageQuery {
+    session_id: String,
+}
+
+/// Open an SSE stream. The first event tells the client where to POST its
+/// messages; server messages follow as `message` events.
+async fn sse_connect(
+    State(state): State<SseState>,
+    client: Option<Extension<Arc<Client>>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let client_id = Uuid::new_v4().to_string();
+    let (inbound_tx, inbound_rx) = mpsc::channel(SSE_CHANNEL_CAPACITY);
+    let (outbound_tx, outbound_rx) = mpsc::channel(SSE_CHANNEL_CAPACITY);
+    state
+        .clients
+        .lock()
+        .unwrap()
+        .insert(
+            client_id.clone(),
+            SseClient {
+                client: client.map(|Extension(client)| client),
+                inbound: inbound_tx,
+            },
+        );
+    tracing::info!(client_id = %client_id, "SSE client connected");
+
+    let transport = ChannelTransport {
+        outbound: outbound_tx.clone(),
+        inbound: inbound_rx,
+    };
+    let id = client_id.clone();
+    tokio::spawn(async move {
+        let connection = async {
+            let running = state
+                .server
+                .for_connection()
+                .serve_with_ct(transport, state.shutdown.child_token())
+                .await
+                .map_err(|e| e.to_string())?;
+            running.waiting().await.map_err(|e| e.to_string())
+        };
+        // Dropping the connection when the client goes away cancels it
+        tokio::select! {
+            result = connection => {
+                if let Err(e) = result {
+                    tracing::warn!(client_id = %id, "SSE connection failed: {}", e);
+                }
+            }
+            _ = outbound_tx.closed() => {}
+        }
+        state.clients.lock().unwrap().remove(&id);
+        tracing::info!(client_id = %id, "SSE client disconnected");
+    });
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("/message?sessionId={}", client_id));
+    let messages = ReceiverStream::new(outbound_rx).map(|message: ServerJsonRpcMessage| {
+        Ok(Event::default()
+            .event("message")
+            .json_data(message)
+            .unwrap_or_else(|e| Event::default().comment(format!("serialization error: {}", e))))
+    });
+    Sse::new(tokio_stream::once(Ok(endpoint)).chain(messages)).keep_alive(KeepAlive::default())
+}
+
+/// Deliver a client message to its SSE connection
+async fn sse_message(
+    State(state): State<SseState>,
+    Query(query): Query<MessageQuery>,
+    mut parts: Parts,
+    Json(mut message): Json<ClientJsonRpcMessage>,
+) -> StatusCode {
+    let inbound = {
+        let clients = state.clients.lock().unwrap();
+        let Some(sse) = clients.get(&query.session_id) else {
+            return StatusCode::NOT_FOUND;
+        };
+        // Only the client that opened the stream may post to it
+        let sender = parts.extensions.get::<Arc<Client>>();
+        if sse.client.as_ref().map(|c| &c.name) != sender.map(|c| &c.name) {
+            return StatusCode::FORBIDDEN;
+        }
+        sse.inbound.clone()
+    };
+
+    // Expose the HTTP request to tool handlers, like the Streamable HTTP
+    // transport does
+    parts.extensions.insert(ConnectionId(query.session_id));
+    match &mut message {
+        ClientJsonRpcMessage::Request(request) => {
+            request.request.extensions_mut().insert(parts);
+        }
+        ClientJsonRpcMessage::Notification(notification) => {
+            notification.notification.extensions_mut().insert(parts);
+        }
+        _ => {}
+    }
+    match inbound.send(message).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::NOT_FOUND,
+    }
+}
+
+/// MCP transport over a pair of channels bridged to an SSE stream and its
+/// POST endpoint
+struct ChannelTransport {
+    outbound: mpsc::Sender<ServerJsonRpcMessage>,
+    inbound: mpsc::Receiver<ClientJsonRpcMessage>,
+}
+
+impl rmcp::transport::Transport<RoleServer> for ChannelTransport {
+    type Error = std::io::Error;
+
+    fn send(
+        &mut self,
+        item: TxJsonRpcMessage<RoleServer>,
+    ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send + 'static {
+        let outbound = self.outbound.clone();
+        async move {
+            outbound
+                .send(item)
+                .await
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "SSE stream closed"))
+        }
+    }
+
+    fn receive(
+        &mut self,
+    ) -> impl std::future::Future<Output = Option<RxJsonRpcMessage<RoleServer>>> + Send {
+        self.inbound.recv()
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.inbound.close();
+        Ok(())
+    }
+}
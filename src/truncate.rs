@@ -0,0 +1,335 @@
+/// Default most lines of output `execute` returns
+const DEFAULT_MAX_LINES: usize = 500;
+
+/// Default most bytes of output `execute` returns
+const DEFAULT_MAX_BYTES: usize = 50 * 1024;
+
+/// Share of a limit spent on the start of the output; the rest goes to its
+/// end, where errors and summaries usually are
+const HEAD_SHARE: usize = 4;
+
+/// Shortest run of similar lines collapsed by deduplication
+const MIN_SIMILAR_RUN: usize = 4;
+
+/// How much of a command's output to return
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputLimits {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
+    /// In output too long for the limits, collapse runs of lines that repeat
+    /// or differ only in their numbers, e.g. progress
+    pub dedup: bool,
+}
+
+impl OutputLimits {
+    /// Read the defaults from `MCP_TERMINAL_OUTPUT_MAX_LINES` (default 500)
+    /// and `MCP_TERMINAL_OUTPUT_MAX_BYTES` (default 50 KB); `0` disables a
+    /// limit
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            max_lines: env_limit("MCP_TERMINAL_OUTPUT_MAX_LINES", DEFAULT_MAX_LINES)?,
+            max_bytes: env_limit("MCP_TERMINAL_OUTPUT_MAX_BYTES", DEFAULT_MAX_BYTES)?,
+            dedup: true,
+        })
+    }
+
+    /// These limits overridden by the ones a tool call gave; `0` disables a
+    /// limit
+    pub fn with(self, max_lines: Option<usize>, max_bytes: Option<usize>, dedup: Option<bool>) -> Self {
+        let limit = |given: Option<usize>, default| match given {
+            Some(0) => None,
+            Some(n) => Some(n),
+            None => default,
+        };
+        Self {
+            max_lines: limit(max_lines, self.max_lines),
+            max_bytes: limit(max_bytes, self.max_bytes),
+            dedup: dedup.unwrap_or(self.dedup),
+        }
+    }
+}
+
+/// Output cut down to the limits, with markers where lines were left out
+#[derive(Debug)]
+pub struct Truncated {
+    pub text: String,
+    /// Lines dropped from the middle to fit the limits
+    pub omitted_lines: usize,
+    /// Lines collapsed by deduplication
+    pub similar_lines: usize,
+}
+
+impl Truncated {
+    pub fn is_complete(&self) -> bool {
+        self.omitted_lines == 0 && self.similar_lines == 0
+    }
+
+    /// What was left out, e.g. "1200 lines omitted, 80 similar lines collapsed"
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.omitted_lines > 0 {
+            parts.push(format!("{} lines omitted", self.omitted_lines));
+        }
+        if self.similar_lines > 0 {
+            parts.push(format!("{} similar lines collapsed", self.similar_lines));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Fit output into `limits`: if it is too long, collapse runs of similar
+/// lines, then keep the start and end of what is still too long
+pub fn truncate(output: &str, limits: &OutputLimits) -> Truncated {
+    let mut lines: Vec<String> = output.lines().map(str::to_string).collect();
+    let too_long = limits.max_lines.is_some_and(|max| lines.len() > max)
+        || limits.max_bytes.is_some_and(|max| output.len() > max);
+
+    // Output that fits is returned whole, so it is never reported as cut
+    let mut similar_lines = 0;
+    if limits.dedup && too_long {
+        (lines, similar_lines) = collapse_similar(lines);
+    }
+
+    let mut omitted_lines = 0;
+    if let Some(max) = limits.max_lines {
+        if lines.len() > max {
+            let head = max / HEAD_SHARE;
+            let tail = max - head;
+            omitted_lines = lines.len() - max;
+            let marker = omitted_marker(omitted_lines);
+            lines.splice(head..lines.len() - tail, [marker]);
+        }
+    }
+
+    let mut text = lines.join("\n");
+    if let Some(max) = limits.max_bytes {
+        if text.len() > max {
+            let (cut, omitted) = cut_bytes(&text, max);
+            text = cut;
+            omitted_lines += omitted;
+        }
+    }
+
+    Truncated {
+        text,
+        omitted_lines,
+        similar_lines,
+    }
+}
+
+fn omitted_marker(lines: usize) -> String {
+    format!("[... {} lines omitted ...]", lines)
+}
+
+/// Whether two lines are repeats, or the same text with different numbers
+/// (e.g. `Downloading 45%`). Lines of numbers alone are data, not similar.
+fn similar(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let shape = |line: &str| line.replace(|c: char| c.is_ascii_digit(), "");
+    let a_shape = shape(a);
+    a_shape.chars().any(char::is_alphabetic) && a_shape == shape(b)
+}
+
+/// Replace each run of lines similar to its first with its first and last
+/// line and a marker between them. Returns the lines and how many were
+/// collapsed.
+fn collapse_similar(lines: Vec<String>) -> (Vec<String>, usize) {
+    let mut collapsed = Vec::with_capacity(lines.len());
+    let mut similar_lines = 0;
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start + 1;
+        while end < lines.len() && similar(&lines[start], &lines[end]) {
+            end += 1;
+        }
+        if end - start >= MIN_SIMILAR_RUN {
+            collapsed.push(lines[start].clone());
+            collapsed.push(format!("[... {} similar lines omitted ...]", end - start - 2));
+            collapsed.push(lines[end - 1].clone());
+            similar_lines += end - start - 2;
+        } else {
+            collapsed.extend_from_slice(&lines[start..end]);
+        }
+        start = end;
+    }
+    (collapsed, similar_lines)
+}
+
+/// Keep the start and end of `text` within about `max` bytes, cut at line
+/// breaks where there are any. Returns the text and how many lines it lost.
+fn cut_bytes(text: &str, max: usize) -> (String, usize) {
+    let head_max = floor_char_boundary(text, max / HEAD_SHARE);
+    let head_end = text[..head_max].rfind('\n').map_or(head_max, |i| i + 1);
+    let tail_min = ceil_char_boundary(text, text.len() - (max - max / HEAD_SHARE));
+    let tail_start = text[tail_min..]
+        .find('\n')
+        .map_or(tail_min, |i| tail_min + i + 1)
+        .max(head_end);
+
+    let omitted = &text[head_end..tail_start];
+    let omitted_lines = omitted.matches('\n').count().max(1);
+    let cut = format!(
+        "{}[... {} bytes in {} lines omitted ...]\n{}",
+        &text[..head_end],
+        omitted.len(),
+        omitted_lines,
+        &text[tail_start..]
+    );
+    (cut, omitted_lines)
+}
+
+fn floor_char_boundary(text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+fn ceil_char_boundary(text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+/// Parse a limit from the environment; unset means `default`, `0` no limit
+fn env_limit(name: &str, default: usize) -> Result<Option<usize>, String> {
+    match std::env::var(name) {
+        Err(_) => Ok(Some(default)),
+        Ok(value) if value.is_empty() => Ok(Some(default)),
+        Ok(value) => match value.parse::<usize>() {
+            Ok(0) => Ok(None),
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(format!("Invalid {}: {}", name, value)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_lines: Option<usize>, max_bytes: Option<usize>) -> OutputLimits {
+        OutputLimits {
+            max_lines,
+            max_bytes,
+            dedup: true,
+        }
+    }
+
+    /// Lines of numbers alone, which deduplication leaves alone
+    fn numbered(n: usize) -> String {
+        (1..=n).map(|i| i.to_string()).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn keeps_empty_output() {
+        let truncated = truncate("", &limits(Some(10), Some(100)));
+        assert_eq!(truncated.text, "");
+        assert!(truncated.is_complete());
+        assert_eq!(truncated.summary(), "");
+    }
+
+    #[test]
+    fn keeps_output_exactly_at_the_limits() {
+        let output = numbered(10);
+        let truncated = truncate(&output, &limits(Some(10), Some(output.len())));
+        assert_eq!(truncated.text, output);
+        assert!(truncated.is_complete());
+    }
+
+    #[test]
+    fn keeps_head_and_tail_of_too_many_lines() {
+        let truncated = truncate(&numbered(11), &limits(Some(8), None));
+        let lines: Vec<&str> = truncated.text.lines().collect();
+        assert_eq!(
+            lines,
+            ["1", "2", "[... 3 lines omitted ...]", "6", "7", "8", "9", "10", "11"]
+        );
+        assert_eq!(truncated.omitted_lines, 3);
+        assert_eq!(truncated.summary(), "3 lines omitted");
+    }
+
+    #[test]
+    fn cuts_too_many_bytes_at_line_breaks() {
+        let output = numbered(100);
+        let truncated = truncate(&output, &limits(None, Some(200)));
+        assert!(truncated.text.starts_with("1\n2\n"));
+        assert!(truncated.text.ends_with("\n99\n100"));
+        assert!(truncated.text.contains(" lines omitted ...]\n"));
+        assert!(truncated.text.len() <= 200 + 64, "{}", truncated.text.len());
+        assert!(!truncated.is_complete());
+    }
+
+    #[test]
+    fn cuts_bytes_on_char_boundaries() {
+        // No line breaks to cut at, and limits that fall inside characters
+        let output = "é".repeat(100);
+        for max in [2, 7, 51, 199] {
+            let truncated = truncate(&output, &limits(None, Some(max)));
+            assert!(truncated.text.contains("bytes in 1 lines omitted"), "{}", truncated.text);
+            assert!(truncated.text.ends_with('é'), "{}", truncated.text);
+            assert_eq!(truncated.omitted_lines, 1);
+        }
+    }
+
+    #[test]
+    fn leaves_repeats_alone_in_output_that_fits() {
+        let output = ["ok"; 6].join("\n");
+        let truncated = truncate(&output, &limits(Some(10), None));
+        assert_eq!(truncated.text, output);
+        assert!(truncated.is_complete());
+    }
+
+    #[test]
+    fn collapses_similar_lines_in_output_too_long() {
+        let mut lines: Vec<String> = (1..=20).map(|i| format!("Downloading {}%", i * 5)).collect();
+        lines.push("done".to_string());
+        let truncated = truncate(&lines.join("\n"), &limits(Some(10), None));
+        assert_eq!(
+            truncated.text,
+            "Downloading 5%\n[... 18 similar lines omitted ...]\nDownloading 100%\ndone"
+        );
+        assert_eq!(truncated.similar_lines, 18);
+        assert_eq!(truncated.omitted_lines, 0);
+        assert_eq!(truncated.summary(), "18 similar lines collapsed");
+    }
+
+    #[test]
+    fn keeps_runs_shorter_than_the_minimum() {
+        let lines = vec!["a 1".to_string(), "a 2".to_string(), "a 3".to_string()];
+        assert_eq!(collapse_similar(lines.clone()), (lines, 0));
+    }
+
+    #[test]
+    fn does_not_collapse_when_dedup_is_off() {
+        let output = ["same"; 20].join("\n");
+        let limits = OutputLimits {
+            dedup: false,
+            ..limits(Some(10), None)
+        };
+        let truncated = truncate(&output, &limits);
+        assert_eq!(truncated.similar_lines, 0);
+        assert_eq!(truncated.omitted_lines, 10);
+    }
+
+    #[test]
+    fn tells_similar_lines_from_data() {
+        assert!(similar("Downloading 45%", "Downloading 46%"));
+        assert!(similar("same", "same"));
+        assert!(!similar("123", "456"));
+        assert!(!similar("test a ... ok", "test b ... ok"));
+    }
+
+    #[test]
+    fn overrides_limits_per_call() {
+        let defaults = limits(Some(500), Some(1024));
+        assert_eq!(defaults.with(None, None, None), defaults);
+        let given = defaults.with(Some(0), Some(10), Some(false));
+        assert_eq!(given.max_lines, None);
+        assert_eq!(given.max_bytes, Some(10));
+        assert!(!given.dedup);
+    }
+}
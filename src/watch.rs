@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Maximum number of hits remembered per session
+const MAX_HITS: usize = 1000;
+
+/// Minimum time between two input or signal actions of the same trigger, so
+/// a trigger matching its own echoed input can't loop
+const ACTION_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// What a trigger does when a line of output matches it. Every match is
+/// recorded as a hit regardless of the action.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum WatchAction {
+    /// Only record the hit
+    Record,
+    /// Send the client a session notification
+    Notify,
+    /// Write input to the session
+    SendInput { input: String },
+    /// Send a signal to the session's processes
+    Signal { signal: String },
+}
+
+impl WatchAction {
+    /// Build an action from its name and the argument it needs
+    pub fn parse(action: &str, input: Option<String>, signal: Option<String>) -> Result<Self, String> {
+        match action {
+            "record" => Ok(Self::Record),
+            "notify" => Ok(Self::Notify),
+            "send_input" => input
+                .map(|input| Self::SendInput { input })
+                .ok_or_else(|| "The send_input action needs input".to_string()),
+            "signal" => {
+                let signal = signal.ok_or_else(|| "The signal action needs a signal".to_string())?;
+                signal_number(&signal)?;
+                Ok(Self::Signal { signal })
+            }
+            other => Err(format!(
+                "Unknown watch action {}; use record, notify, send_input or signal",
+                other
+            )),
+        }
+    }
+}
+
+/// Number of a signal given by name, with or without the `SIG` prefix
+pub fn signal_number(name: &str) -> Result<libc::c_int, String> {
+    let upper = name.to_ascii_uppercase();
+    Ok(match upper.strip_prefix("SIG").unwrap_or(&upper) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "TSTP" => libc::SIGTSTP,
+        "WINCH" => libc::SIGWINCH,
+        _ => return Err(format!("Unsupported signal {}", name)),
+    })
+}
+
+/// A regex trigger on a session's output
+struct Trigger {
+    id: u32,
+    pattern: Regex,
+    action: WatchAction,
+    /// Remove the trigger after its first hit
+    once: bool,
+    hits: u64,
+    last_action: Option<Instant>,
+}
+
+/// Public trigger metadata
+#[derive(serde::Serialize)]
+pub struct TriggerInfo {
+    pub trigger_id: u32,
+    pub pattern: String,
+    #[serde(flatten)]
+    pub action: WatchAction,
+    pub once: bool,
+    pub hits: u64,
+}
+
+/// A line of output that matched a trigger
+#[derive(Clone, serde::Serialize)]
+pub struct WatchHit {
+    /// Increasing number of the hit within the session
+    pub hit_id: u64,
+    pub trigger_id: u32,
+    pub at: DateTime<Utc>,
+    pub line: String,
+}
+
+/// An action to run for a hit
+pub struct Fired {
+    pub trigger_id: u32,
+    pub pattern: String,
+    pub action: WatchAction,
+}
+
+/// Triggers of a session and their recent hits
+#[derive(Default)]
+pub struct Watchers {
+    triggers: Vec<Trigger>,
+    hits: VecDeque<WatchHit>,
+    next_trigger_id: u32,
+    next_hit_id: u64,
+}
+
+impl Watchers {
+    /// Add a trigger. Returns its ID.
+    pub fn add(&mut self, pattern: &str, action: WatchAction, once: bool) -> Result<u32, String> {
+        let pattern = Regex::new(pattern).map_err(|e| format!("Invalid watch pattern {}: {}", pattern, e))?;
+        self.next_trigger_id += 1;
+        self.triggers.push(Trigger {
+            id: self.next_trigger_id,
+            pattern,
+            action,
+            once,
+            hits: 0,
+            last_action: None,
+        });
+        Ok(self.next_trigger_id)
+    }
+
+    /// Remove a trigger. Returns whether it existed.
+    pub fn remove(&mut self, trigger_id: u32) -> bool {
+        let before = self.triggers.len();
+        self.triggers.retain(|t| t.id != trigger_id);
+        self.triggers.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    /// Record the hits of a line of output. Returns the actions to run.
+    pub fn match_line(&mut self, line: &str) -> Vec<Fired> {
+        let now = Instant::now();
+        let mut fired = Vec::new();
+        for trigger in self.triggers.iter_mut().filter(|t| t.pattern.is_match(line)) {
+            trigger.hits += 1;
+            self.next_hit_id += 1;
+            self.hits.push_back(WatchHit {
+                hit_id: self.next_hit_id,
+                trigger_id: trigger.id,
+                at: Utc::now(),
+                line: line.to_string(),
+            });
+
+            let cooling_down = matches!(trigger.action, WatchAction::SendInput { .. } | WatchAction::Signal { .. })
+                && trigger.last_action.is_some_and(|t| now.duration_since(t) < ACTION_COOLDOWN);
+            if !matches!(trigger.action, WatchAction::Record) && !cooling_down {
+                trigger.last_action = Some(now);
+                fired.push(Fired {
+                    trigger_id: trigger.id,
+                    pattern: trigger.pattern.to_string(),
+                    action: trigger.action.clone(),
+                });
+            }
+        }
+        self.triggers.retain(|t| !(t.once && t.hits > 0));
+        while self.hits.len() > MAX_HITS {
+            self.hits.pop_front();
+        }
+        fired
+    }
+
+    pub fn triggers(&self) -> Vec<TriggerInfo> {
+        self.triggers
+            .iter()
+            .map(|t| TriggerInfo {
+                trigger_id: t.id,
+                pattern: t.pattern.to_string(),
+                action: t.action.clone(),
+                once: t.once,
+                hits: t.hits,
+            })
+            .collect()
+    }
+
+    /// Hits after `after_hit_id`, optionally only those of one trigger
+    pub fn hits(&self, after_hit_id: Option<u64>, trigger_id: Option<u32>) -> Vec<WatchHit> {
+        self.hits
+            .iter()
+            .filter(|h| after_hit_id.is_none_or(|after| h.hit_id > after))
+            .filter(|h| trigger_id.is_none_or(|id| h.trigger_id == id))
+            .cloned()
+            .collect()
+    }
+}